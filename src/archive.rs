@@ -0,0 +1,65 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::Connection;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::db;
+
+/// Gzipped copies of every fetched page, stored in the `raw_pages` table so
+/// that parses can be audited and redone after the site has changed. The
+/// connection is locked for every access so that concurrent scrapers can
+/// share the archive.
+pub struct Archive {
+    conn: Mutex<Connection>,
+}
+
+impl Archive {
+    /// Opens a separate connection to the database at `path`, so that the
+    /// archive can be written to while the scrapers hold the main one.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(30))?;
+
+        Ok(Archive {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn store(
+        &self,
+        source: &str,
+        url: &str,
+        body: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let body = compress(body)?;
+        db::insert_raw_page(&self.conn.lock().unwrap(), source, url, &body)?;
+
+        Ok(())
+    }
+
+    /// The most recently archived copy of `url`, if any.
+    pub fn load(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let body = db::find_raw_page(&self.conn.lock().unwrap(), url)?;
+
+        match body {
+            Some(body) => Ok(Some(decompress(&body)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+pub fn compress(body: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body.as_bytes())?;
+    encoder.finish()
+}
+
+pub fn decompress(body: &[u8]) -> std::io::Result<String> {
+    let mut decoded = String::new();
+    GzDecoder::new(body).read_to_string(&mut decoded)?;
+
+    Ok(decoded)
+}
@@ -0,0 +1,262 @@
+use chrono::prelude::{NaiveDate, Utc};
+use rusqlite::{Connection, Result, Transaction};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::db::{self, Upserted};
+
+/// Suggestions less similar than this are mostly noise
+const MIN_SIMILARITY: f64 = 0.85;
+
+/// Printings read per transaction during an import
+const BATCH_SIZE: usize = 1000;
+
+/// Prices are recorded under the day of the import, so fetching every day
+/// builds up their history.
+pub fn fetch_scryfall_cards(conn: &Connection) -> Result<()> {
+    println!("Fetching cards...");
+    match scryfall::bulk::oracle_cards() {
+        Ok(cards) => {
+            println!("Received cards, starting to update database");
+            let mut import = Import::new(conn)?;
+
+            for card in cards.flatten() {
+                import.card(&card)?;
+            }
+
+            import.finish()?;
+        }
+        Err(e) => {
+            println!("Failed to fetch cards: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Imports a bulk data file downloaded from Scryfall, either `oracle-cards`
+/// or `default-cards`. The file is read one card at a time, `default-cards`
+/// is too large to keep in memory as a whole. The card data comes from the
+/// first printing of a card, the prices from the cheapest ones.
+pub fn import_scryfall_cards(
+    conn: &Connection,
+    path: &Path,
+) -> Result<ImportStats, Box<dyn std::error::Error>> {
+    println!("Importing cards from {}...", path.display());
+    let reader = BufReader::new(File::open(path)?);
+    let mut import = Import::new(conn)?;
+
+    serde_json::Deserializer::from_reader(reader)
+        .deserialize_seq(CardsVisitor(|card: scryfall::Card| import.card(&card)))?;
+
+    Ok(import.finish()?)
+}
+
+/// What an import did with the cards it read. Printings of a card that was
+/// already written only add their prices and aren't counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportStats {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+/// Upserts cards in transactions of [`BATCH_SIZE`] printings and counts what
+/// happened to them.
+struct Import<'a> {
+    conn: &'a Connection,
+    tx: Option<Transaction<'a>>,
+    today: NaiveDate,
+    /// Cards written so far by oracle id, `default-cards` lists every
+    /// printing of a card and only the first one is written
+    card_ids: HashMap<String, i64>,
+    printings: usize,
+    stats: ImportStats,
+}
+
+impl<'a> Import<'a> {
+    fn new(conn: &'a Connection) -> Result<Self> {
+        let today = Utc::now().naive_utc().date();
+        let tx = conn.unchecked_transaction()?;
+        db::delete_card_prices(&tx, today)?;
+
+        Ok(Import {
+            conn,
+            tx: Some(tx),
+            today,
+            card_ids: HashMap::new(),
+            printings: 0,
+            stats: ImportStats::default(),
+        })
+    }
+
+    /// A card that fails to save is counted and skipped, along with anything
+    /// it wrote before failing. Errors are only returned for the transaction
+    /// itself.
+    fn card(&mut self, card: &scryfall::Card) -> Result<()> {
+        let tx = match &mut self.tx {
+            Some(tx) => tx,
+            None => self.tx.insert(self.conn.unchecked_transaction()?),
+        };
+
+        let oracle_id = card.oracle_id.to_string();
+        let written = self.card_ids.get(&oracle_id).copied();
+
+        // Dropping the savepoint rolls the card back
+        let upserted = {
+            let savepoint = tx.savepoint()?;
+            let upserted = match written {
+                Some(card_id) => Ok((card_id, None)),
+                None => db::upsert_card(&savepoint, card)
+                    .map(|(card_id, upserted)| (card_id, Some(upserted))),
+            }
+            .and_then(|(card_id, upserted)| {
+                db::insert_card_price(&savepoint, card_id, self.today, &card.prices)?;
+                Ok((card_id, upserted))
+            });
+            if upserted.is_ok() {
+                savepoint.commit()?;
+            }
+            upserted
+        };
+
+        match upserted {
+            Ok((card_id, upserted)) => {
+                self.card_ids.insert(oracle_id, card_id);
+                match upserted {
+                    Some(Upserted::Inserted) => self.stats.inserted += 1,
+                    Some(Upserted::Updated) => self.stats.updated += 1,
+                    Some(Upserted::Unchanged) => self.stats.unchanged += 1,
+                    None => {}
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to upsert {}: {}", card.name, e);
+                self.stats.failed += 1;
+            }
+        }
+
+        self.printings += 1;
+        if self.printings.is_multiple_of(BATCH_SIZE) {
+            if let Some(tx) = self.tx.take() {
+                tx.commit()?;
+            }
+            println!("Read {} printings...", self.printings);
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ImportStats> {
+        if let Some(tx) = self.tx.take() {
+            tx.commit()?;
+        }
+
+        let stats = self.stats;
+        println!(
+            "Inserted {} cards, updated {}, {} unchanged, {} failed.",
+            stats.inserted, stats.updated, stats.unchanged, stats.failed
+        );
+
+        Ok(stats)
+    }
+}
+
+/// Hands the cards of a bulk data array to a callback as they are parsed.
+/// Cards that don't parse are skipped like they are for the bulk download.
+struct CardsVisitor<F>(F);
+
+impl<'de, F: FnMut(scryfall::Card) -> Result<()>> Visitor<'de> for CardsVisitor<F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of Scryfall cards")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(value) = seq.next_element::<serde_json::Value>()? {
+            match serde_json::from_value(value) {
+                Ok(card) => (self.0)(card).map_err(de::Error::custom)?,
+                Err(e) => eprintln!("Failed to parse card: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Spells a card name the way Scryfall does, as far as that is possible
+/// without card data. Split cards are joined with ` // `, `Æ` is written
+/// `Ae`, quotes are straight and whitespace is collapsed.
+pub fn normalize_name(name: &str) -> String {
+    let name = name
+        .replace('Æ', "Ae")
+        .replace('æ', "ae")
+        .replace(['‘', '’'], "'");
+
+    name.split('/')
+        .map(|face| face.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|face| !face.is_empty())
+        .collect::<Vec<_>>()
+        .join(" // ")
+}
+
+/// Spellings of the same card share a key, which is what `card_aliases`
+/// are looked up by.
+pub fn name_key(name: &str) -> String {
+    normalize_name(name).to_lowercase()
+}
+
+/// Cards with Scryfall data whose names are closest to `name`, best first,
+/// with their similarity. Double-faced cards are also compared by their
+/// front face.
+pub fn suggest<'a>(
+    name: &str,
+    candidates: &'a [(i64, String)],
+    limit: usize,
+) -> Vec<(f64, i64, &'a str)> {
+    let key = name_key(name);
+
+    let mut suggestions = candidates
+        .iter()
+        .map(|(id, candidate)| {
+            let front = candidate.split(" // ").next().unwrap_or(candidate);
+            let score = strsim::jaro_winkler(&key, &name_key(candidate))
+                .max(strsim::jaro_winkler(&key, &name_key(front)));
+
+            (score, *id, candidate.as_str())
+        })
+        .filter(|(score, _, _)| *score >= MIN_SIMILARITY)
+        .collect::<Vec<_>>();
+
+    suggestions.sort_by(|a, b| b.0.total_cmp(&a.0));
+    suggestions.truncate(limit);
+
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_name_spells_names_like_scryfall() {
+        assert_eq!(normalize_name("Fire/Ice"), "Fire // Ice");
+        assert_eq!(normalize_name(" Fire  //  Ice "), "Fire // Ice");
+        assert_eq!(normalize_name("Æther Vial"), "Aether Vial");
+        assert_eq!(normalize_name("Jötun Grunt"), "Jötun Grunt");
+        assert_eq!(normalize_name("Gaea’s Cradle"), "Gaea's Cradle");
+        assert_eq!(normalize_name("Lightning\tBolt"), "Lightning Bolt");
+    }
+
+    #[test]
+    fn name_key_ignores_case() {
+        assert_eq!(name_key("fire / ice"), name_key("Fire // Ice"));
+        assert_ne!(name_key("Fire // Ice"), name_key("Fire"));
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::prelude::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::deck::Format;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub database: PathBuf,
+    /// Keep a compressed copy of every fetched page in `raw_pages`
+    pub archive: bool,
+    /// Events scraped at once per source. Requests to a host are still
+    /// limited by its `delay` and `burst`.
+    pub concurrency: usize,
+    pub mtgo: SourceConfig,
+    pub tcdecks: SourceConfig,
+    pub retry: RetryConfig,
+    pub http: HttpConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct SourceConfig {
+    pub enabled: bool,
+    pub base_url: String,
+    /// Delay between requests to the same host, in milliseconds
+    pub delay: u64,
+    /// Requests allowed at once before `delay` kicks in
+    pub burst: u32,
+    /// Request timeout, in seconds
+    pub timeout: u64,
+    pub user_agent: Option<String>,
+    /// Check the site's robots.txt before every request
+    pub respect_robots: bool,
+    /// Use this robots.txt instead of fetching it from the site
+    pub robots_txt: Option<PathBuf>,
+    /// Formats to scrape, all supported formats when empty
+    pub formats: Vec<Format>,
+    /// Oldest event date to discover when backfilling
+    pub start_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Give up on a link after this many failed attempts
+    pub max_attempts: u32,
+    /// Wait after the first failure, in seconds, doubled on every attempt
+    pub backoff: u64,
+    /// Parse errors usually need a parser fix, so they aren't retried by default
+    pub retry_parse_errors: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpMode {
+    /// Fetch pages from the sites
+    Live,
+    /// Fetch pages from the sites and save them as fixtures
+    Record,
+    /// Serve previously recorded fixtures, never touch the network
+    Replay,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub mode: HttpMode,
+    /// Directory for recorded responses
+    pub fixtures: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database: PathBuf::from("decklists.db"),
+            archive: true,
+            concurrency: 4,
+            mtgo: SourceConfig {
+                base_url: "https://magic.wizards.com".to_owned(),
+                delay: 1000,
+                ..Default::default()
+            },
+            tcdecks: SourceConfig {
+                base_url: "https://www.tcdecks.net".to_owned(),
+                delay: 5000,
+                ..Default::default()
+            },
+            retry: RetryConfig::default(),
+            http: HttpConfig::default(),
+        }
+    }
+}
+
+impl Default for SourceConfig {
+    fn default() -> Self {
+        SourceConfig {
+            enabled: true,
+            base_url: String::new(),
+            delay: 1000,
+            burst: 1,
+            timeout: 60,
+            user_agent: None,
+            respect_robots: true,
+            robots_txt: None,
+            formats: Vec::new(),
+            start_date: None,
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 5,
+            backoff: 3600,
+            retry_parse_errors: false,
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            mode: HttpMode::Live,
+            fixtures: PathBuf::from("fixtures"),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config from `path`, or from `config.toml` in the working
+    /// directory if it exists. Falls back to the built-in defaults.
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn std::error::Error>> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Path::new(DEFAULT_CONFIG_PATH),
+            None => return Ok(Config::default()),
+        };
+
+        let contents = fs::read_to_string(path)?;
+
+        Config::parse(&contents)
+    }
+
+    /// Keys missing from `contents` keep their values from
+    /// [`Config::default`]. Merging over the defaults rather than relying on
+    /// `#[serde(default)]` keeps the per-site defaults of a partial `[mtgo]`
+    /// or `[tcdecks]` section, e.g. its `base_url` and `delay`.
+    pub fn parse(contents: &str) -> Result<Config, Box<dyn std::error::Error>> {
+        let mut config = toml::Table::try_from(Config::default())?;
+        merge(&mut config, toml::from_str(contents)?);
+
+        Ok(config.try_into()?)
+    }
+}
+
+/// Overwrites the keys of `base` with the ones in `overrides`, descending
+/// into tables present in both.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl SourceConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout)
+    }
+
+    pub fn is_format_enabled(&self, format: &Format) -> bool {
+        self.formats.is_empty() || self.formats.contains(format)
+    }
+}
+
+impl RetryConfig {
+    /// How long to wait before the next attempt after `attempts` failures.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(16);
+        Duration::from_secs(self.backoff.saturating_mul(1 << exponent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_source_section_keeps_site_defaults() {
+        let config = Config::parse("[tcdecks]\ndelay = 3000\n\n[mtgo]\nenabled = false\n").unwrap();
+
+        assert_eq!(config.tcdecks.base_url, "https://www.tcdecks.net");
+        assert_eq!(config.tcdecks.delay, 3000);
+        assert_eq!(config.mtgo.base_url, "https://magic.wizards.com");
+        assert_eq!(config.mtgo.delay, 1000);
+        assert!(!config.mtgo.enabled);
+    }
+
+    #[test]
+    fn example_config_parses_to_the_defaults() {
+        let example = Config::parse(include_str!("../config.example.toml")).unwrap();
+        let defaults = Config::default();
+
+        assert_eq!(example.tcdecks.base_url, defaults.tcdecks.base_url);
+        assert_eq!(example.tcdecks.delay, defaults.tcdecks.delay);
+        assert_eq!(example.mtgo.delay, defaults.mtgo.delay);
+        assert_eq!(example.retry.max_attempts, defaults.retry.max_attempts);
+        assert_eq!(example.http.mode, defaults.http.mode);
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        let retry = RetryConfig::default();
+
+        assert_eq!(retry.backoff(0), Duration::from_secs(3600));
+        assert_eq!(retry.backoff(1), Duration::from_secs(3600));
+        assert_eq!(retry.backoff(2), Duration::from_secs(7200));
+        assert_eq!(retry.backoff(4), Duration::from_secs(28800));
+    }
+
+    #[test]
+    fn backoff_does_not_overflow() {
+        let retry = RetryConfig {
+            backoff: u64::MAX / 2,
+            ..Default::default()
+        };
+
+        assert_eq!(retry.backoff(u32::MAX), Duration::from_secs(u64::MAX));
+        assert_eq!(
+            RetryConfig::default().backoff(100),
+            Duration::from_secs(3600 << 16)
+        );
+    }
+}
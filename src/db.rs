@@ -1,105 +1,150 @@
+use chrono::prelude::{NaiveDate, NaiveDateTime};
 use rusqlite::OptionalExtension;
-use rusqlite::{named_params, params, Connection, Result};
-use scryfall::card::Legality;
-use scryfall::format::Format;
+use rusqlite::{params, Connection, Result, Row, ToSql};
+use std::collections::{HashMap, HashSet};
 
-use crate::deck::{Decklist, ScrapedLink};
+use crate::cards;
+use crate::deck::{
+    DeckPrice, Decklist, DecklistFilter, Event, FailureKind, Format, Legality, ScrapedEvent,
+    ScrapedLink,
+};
+use crate::migrations;
+use crate::validate::{CardData, Violation};
 
+/// Brings the schema of the database up to date, see [`migrations`].
 pub fn setup(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS decks (
-                id INTEGER PRIMARY KEY,
-                name TEXT,
-                format TEXT NOT NULL,
-                event TEXT,
-                date TEXT,
-                player TEXT,
-                archetype TEXT,
-                result TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS cards (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE,
-                scryfall_id TEXT,
-                scryfall_url TEXT,
-                cmc REAL,
-                power TEXT,
-                toughness TEXT,
-                type_line TEXT,
-                set_code TEXT,
-                set_name TEXT,
-                colors TEXT,
-                is_premodern_legal INTEGER
-            )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS deck_cards (
-                deck_id INTEGER,
-                card_id INTEGER,
-                count INTEGER,
-                is_sideboard BOOLEAN,
-                FOREIGN KEY(deck_id) REFERENCES decks(id),
-                FOREIGN KEY(card_id) REFERENCES cards(id)
-            )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS scraped_links (
-                id INTEGER PRIMARY KEY,
-                link TEXT NOT NULL UNIQUE,
-                is_success BOOLEAN,
-                error_msg TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-        [],
-    )?;
+    migrations::migrate(conn)?;
 
     Ok(())
 }
 
-pub fn upsert_card(conn: &Connection, card: &scryfall::Card) -> Result<()> {
-    let colors = card.colors.as_ref().and_then(|c| {
-        let v = c
-            .iter()
-            .map(|color| color.to_string())
-            .collect::<Vec<String>>();
-
-        if v.is_empty() {
-            return None;
-        }
+/// What [`upsert_card`] did with a card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upserted {
+    Inserted,
+    Updated,
+    /// Stored already exactly as Scryfall has it, nothing was written
+    Unchanged,
+}
 
-        Some(v.join(","))
-    });
+/// Returns the id of the card. Statements are cached on `conn`, bulk
+/// imports should call this inside a transaction.
+pub fn upsert_card(conn: &Connection, card: &scryfall::Card) -> Result<(i64, Upserted)> {
+    let colors = card.colors.as_deref().and_then(join_colors);
+    let color_identity = join_colors(&card.color_identity);
+    let keywords = Some(card.keywords.join(",")).filter(|keywords| !keywords.is_empty());
+    // `Layout` has no `Display`, its serde name is the one Scryfall uses
+    let layout = serde_json::to_value(card.layout)
+        .ok()
+        .and_then(|layout| layout.as_str().map(|layout| layout.to_owned()));
 
     let is_premodern_legal = card
         .legalities
-        .get(&Format::Premodern)
-        .map(|l| l == &Legality::Legal)
+        .get(&scryfall::format::Format::Premodern)
+        .map(|l| l == &scryfall::card::Legality::Legal)
         .unwrap_or(false);
 
-    let rows = conn.execute(
-        "INSERT OR IGNORE INTO cards (
-                name,
-                scryfall_id,
-                scryfall_url,
-                cmc,
-                power,
-                toughness,
-                type_line,
-                set_code,
-                set_name,
-                colors,
-                is_premodern_legal
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-        params![
+    // Cards are identified by their oracle id, the name changes when Scryfall
+    // renames one. Other rows holding the name, scraped before the card had
+    // Scryfall data or left over from another card, are merged into it. So
+    // are cards without Scryfall data that the name is an alias of.
+    let oracle_id = card.oracle_id.to_string();
+    let existing = conn
+        .prepare_cached(
+            "SELECT id, name, oracle_id IS ?1 FROM cards
+                WHERE oracle_id = ?1
+                   OR name = ?2 COLLATE NOCASE
+                   OR (scryfall_id IS NULL
+                       AND id IN (SELECT card_id FROM card_aliases WHERE alias = ?3))
+                ORDER BY oracle_id IS ?1 DESC,
+                    name = ?2 DESC,
+                    name = ?2 COLLATE NOCASE DESC,
+                    id",
+        )?
+        .query_map(
+            params![&oracle_id, &card.name, cards::name_key(&card.name)],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut changes = 0;
+    if let Some(((card_id, name, has_oracle_id), others)) = existing.split_first() {
+        for (other, _, _) in others {
+            merge_card(conn, *other, *card_id)?;
+        }
+        changes += others.len();
+
+        if !has_oracle_id {
+            changes += conn.execute(
+                "UPDATE cards SET oracle_id = ?2 WHERE id = ?1",
+                params![card_id, &oracle_id],
+            )?;
+        }
+        if name != &card.name {
+            insert_card_alias(conn, name, *card_id)?;
+        }
+    }
+
+    changes += conn
+        .prepare_cached(
+            "INSERT INTO cards (
+                    name,
+                    scryfall_id,
+                    scryfall_url,
+                    cmc,
+                    power,
+                    toughness,
+                    type_line,
+                    set_code,
+                    set_name,
+                    colors,
+                    is_premodern_legal,
+                    mana_cost,
+                    oracle_text,
+                    color_identity,
+                    keywords,
+                    rarity,
+                    oracle_id,
+                    layout
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+                ON CONFLICT(oracle_id) DO UPDATE SET
+                    name                = excluded.name,
+                    scryfall_id         = excluded.scryfall_id,
+                    scryfall_url        = excluded.scryfall_url,
+                    cmc                 = excluded.cmc,
+                    power               = excluded.power,
+                    toughness           = excluded.toughness,
+                    type_line           = excluded.type_line,
+                    set_code            = excluded.set_code,
+                    set_name            = excluded.set_name,
+                    colors              = excluded.colors,
+                    is_premodern_legal  = excluded.is_premodern_legal,
+                    mana_cost           = excluded.mana_cost,
+                    oracle_text         = excluded.oracle_text,
+                    color_identity      = excluded.color_identity,
+                    keywords            = excluded.keywords,
+                    rarity              = excluded.rarity,
+                    oracle_id           = excluded.oracle_id,
+                    layout              = excluded.layout
+                WHERE (
+                    name, scryfall_id, scryfall_url, cmc, power, toughness, type_line,
+                    set_code, set_name, colors, is_premodern_legal, mana_cost, oracle_text,
+                    color_identity, keywords, rarity, oracle_id, layout
+                ) IS NOT (
+                    excluded.name, excluded.scryfall_id, excluded.scryfall_url, excluded.cmc,
+                    excluded.power, excluded.toughness, excluded.type_line, excluded.set_code,
+                    excluded.set_name, excluded.colors, excluded.is_premodern_legal,
+                    excluded.mana_cost, excluded.oracle_text, excluded.color_identity,
+                    excluded.keywords, excluded.rarity, excluded.oracle_id, excluded.layout
+                )",
+        )?
+        .execute(params![
             &card.name,
             &card.id.to_string(),
             &card.scryfall_uri.to_string(),
@@ -111,124 +156,1073 @@ pub fn upsert_card(conn: &Connection, card: &scryfall::Card) -> Result<()> {
             &card.set_name,
             colors,
             is_premodern_legal,
-        ],
-    )?;
+            &card.mana_cost,
+            &card.oracle_text,
+            color_identity,
+            keywords,
+            &card.rarity.to_string(),
+            &oracle_id,
+            layout,
+        ])?;
 
-    if rows == 0 {
-        conn.execute(
-            "UPDATE cards SET
-                    scryfall_id         = ?2,
-                    scryfall_url        = ?3,
-                    cmc                 = ?4,
-                    power               = ?5,
-                    toughness           = ?6,
-                    type_line           = ?7,
-                    set_code            = ?8,
-                    set_name            = ?9,
-                    colors              = ?10,
-                    is_premodern_legal  = ?11
-                WHERE name = ?1",
-            params![
-                &card.name,
-                &card.id.to_string(),
-                &card.scryfall_uri.to_string(),
-                &card.cmc,
-                &card.power,
-                &card.toughness,
-                &card.type_line,
-                &card.set.to_string(),
-                &card.set_name,
-                colors,
-                is_premodern_legal,
-            ],
-        )?;
+    let card_id = match existing.first() {
+        Some((card_id, _, _)) => *card_id,
+        None => conn.last_insert_rowid(),
+    };
+
+    // Split, flip and double-faced cards, in the order Scryfall lists them
+    let faces = card.card_faces.as_deref().unwrap_or_default();
+    for (index, face) in faces.iter().enumerate() {
+        changes += conn
+            .prepare_cached(
+                "INSERT INTO card_faces (
+                        card_id,
+                        face_index,
+                        name,
+                        mana_cost,
+                        type_line,
+                        oracle_text,
+                        colors,
+                        power,
+                        toughness,
+                        loyalty
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                    ON CONFLICT(card_id, face_index) DO UPDATE SET
+                        name        = excluded.name,
+                        mana_cost   = excluded.mana_cost,
+                        type_line   = excluded.type_line,
+                        oracle_text = excluded.oracle_text,
+                        colors      = excluded.colors,
+                        power       = excluded.power,
+                        toughness   = excluded.toughness,
+                        loyalty     = excluded.loyalty
+                    WHERE (
+                        name, mana_cost, type_line, oracle_text, colors, power, toughness,
+                        loyalty
+                    ) IS NOT (
+                        excluded.name, excluded.mana_cost, excluded.type_line,
+                        excluded.oracle_text, excluded.colors, excluded.power,
+                        excluded.toughness, excluded.loyalty
+                    )",
+            )?
+            .execute(params![
+                card_id,
+                index,
+                &face.name,
+                Some(&face.mana_cost).filter(|mana_cost| !mana_cost.is_empty()),
+                &face.type_line,
+                &face.oracle_text,
+                face.colors.as_deref().and_then(join_colors),
+                &face.power,
+                &face.toughness,
+                &face.loyalty,
+            ])?;
+    }
+
+    changes += conn
+        .prepare_cached("DELETE FROM card_faces WHERE card_id = ?1 AND face_index >= ?2")?
+        .execute(params![card_id, faces.len()])?;
+
+    // Every format Scryfall knows, not only the ones we scrape. The names
+    // match `deck::Format` for the ones we do.
+    for (format, legality) in card.legalities.iter() {
+        changes += conn
+            .prepare_cached(
+                "INSERT INTO card_legalities (card_id, format, legality) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(card_id, format) DO UPDATE SET legality = excluded.legality
+                    WHERE legality != excluded.legality",
+            )?
+            .execute(params![
+                card_id,
+                format.to_string(),
+                Legality::from(legality).to_string()
+            ])?;
     }
 
+    let upserted = match existing.first() {
+        None => Upserted::Inserted,
+        Some(_) if changes > 0 => Upserted::Updated,
+        Some(_) => Upserted::Unchanged,
+    };
+
+    Ok((card_id, upserted))
+}
+
+/// Makes `name` look up card `card_id`, e.g. the name it had before Scryfall
+/// renamed it.
+fn insert_card_alias(conn: &Connection, name: &str, card_id: i64) -> Result<usize> {
+    conn.prepare_cached(
+        "INSERT INTO card_aliases (alias, card_id) VALUES (?1, ?2)
+            ON CONFLICT(alias) DO UPDATE SET card_id = excluded.card_id",
+    )?
+    .execute(params![cards::name_key(name), card_id])
+}
+
+/// Records the prices of a card on `date`. `default-cards` has a card once
+/// per printing, the cheapest printing with a price in a currency wins.
+pub fn insert_card_price(
+    conn: &Connection,
+    card_id: i64,
+    date: NaiveDate,
+    prices: &scryfall::card::Price,
+) -> Result<()> {
+    let parse = |price: &Option<String>| price.as_deref().and_then(|p| p.parse::<f64>().ok());
+
+    conn.prepare_cached(
+        "INSERT INTO card_prices (card_id, date, usd, eur, tix) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(card_id, date) DO UPDATE SET
+                usd = min(coalesce(excluded.usd, usd), coalesce(usd, excluded.usd)),
+                eur = min(coalesce(excluded.eur, eur), coalesce(eur, excluded.eur)),
+                tix = min(coalesce(excluded.tix, tix), coalesce(tix, excluded.tix))",
+    )?
+    .execute(params![
+        card_id,
+        date.to_string(),
+        parse(&prices.usd),
+        parse(&prices.eur),
+        parse(&prices.tix),
+    ])?;
+
     Ok(())
 }
 
-pub fn insert_decklist(conn: &Connection, decklist: &Decklist) -> Result<()> {
+/// Clears the prices of `date` so that importing again on the same day
+/// doesn't keep cheaper prices from the earlier import.
+pub fn delete_card_prices(conn: &Connection, date: NaiveDate) -> Result<usize> {
     conn.execute(
-        "INSERT INTO decks (format, event, date, player, archetype, result, name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        "DELETE FROM card_prices WHERE date = ?1",
+        [date.to_string()],
+    )
+}
+
+/// Prices a deck with the latest price of each card on or before `date`.
+pub fn find_deck_price(conn: &Connection, deck_id: i64, date: NaiveDate) -> Result<DeckPrice> {
+    let latest = |currency: &str| {
+        format!(
+            "(SELECT {currency} FROM card_prices
+                WHERE card_id = deck_cards.card_id AND date <= ?2 AND {currency} IS NOT NULL
+                ORDER BY date DESC
+                LIMIT 1)"
+        )
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT count, {}, {}, {} FROM deck_cards WHERE deck_id = ?1",
+        latest("usd"),
+        latest("eur"),
+        latest("tix")
+    ))?;
+
+    let rows = stmt.query_map(params![deck_id, date.to_string()], |row| {
+        Ok((
+            row.get::<_, usize>(0)?,
+            row.get::<_, Option<f64>>(1)?,
+            row.get::<_, Option<f64>>(2)?,
+            row.get::<_, Option<f64>>(3)?,
+        ))
+    })?;
+
+    let mut price = DeckPrice::default();
+
+    for row in rows {
+        let (count, usd, eur, tix) = row?;
+
+        for (total, card_price) in [
+            (&mut price.usd, usd),
+            (&mut price.eur, eur),
+            (&mut price.tix, tix),
+        ] {
+            match card_price {
+                Some(card_price) => total.total += card_price * count as f64,
+                None => total.missing += count,
+            }
+        }
+    }
+
+    Ok(price)
+}
+
+/// Scryfall color letters, e.g. `W,U`.
+fn join_colors(colors: &[scryfall::card::Color]) -> Option<String> {
+    if colors.is_empty() {
+        return None;
+    }
+
+    Some(
+        colors
+            .iter()
+            .map(|color| color.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+pub fn find_card_legality(
+    conn: &Connection,
+    card_id: i64,
+    format: &Format,
+) -> Result<Option<Legality>> {
+    let legality: Option<String> = conn
+        .query_row(
+            "SELECT legality FROM card_legalities WHERE card_id = ?1 AND format = ?2",
+            params![card_id, format.to_string()],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(legality.map(|legality| legality.as_str().into()))
+}
+
+/// Returns false without inserting anything when a deck with the same
+/// fingerprint already exists.
+pub fn insert_decklist(
+    conn: &Connection,
+    event_id: i64,
+    event_link: &str,
+    decklist: &Decklist,
+) -> Result<bool> {
+    let inserted = conn.execute(
+        "INSERT INTO decks (format, event, date, player, archetype, result, name, event_link,
+                fingerprint, event_id, source, source_url, external_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+            ON CONFLICT(fingerprint) DO NOTHING",
         params![
             decklist.format.to_string(),
             decklist.event,
-            decklist.date.and_then(|d| Some(d.to_string())),
+            decklist.date.map(|d| d.to_string()),
             decklist.player,
             decklist.archetype,
             decklist.result,
             decklist.name,
+            event_link,
+            decklist.fingerprint(),
+            event_id,
+            decklist.source,
+            decklist.source_url,
+            decklist.external_id,
         ],
     )?;
 
+    if inserted == 0 {
+        return Ok(false);
+    }
+
     let deck_id = conn.last_insert_rowid();
-    let mut cards_query = conn.prepare("SELECT id FROM cards WHERE name = :name;")?;
 
-    for (count, card) in decklist.mainboard.iter() {
-        let card_id =
-            if let Some(row) = cards_query.query(named_params! { ":name": card })?.next()? {
-                row.get(0)?
-            } else {
-                conn.execute("INSERT INTO cards (name) VALUES (?1)", params![card])?;
-                conn.last_insert_rowid()
-            };
+    let cards = decklist
+        .mainboard
+        .iter()
+        .map(|card| (card, false))
+        .chain(decklist.sideboard.iter().map(|card| (card, true)));
+
+    for ((count, name), is_sideboard) in cards {
+        let card_id = resolve_card(conn, name)?;
 
         conn.execute(
-            "INSERT INTO deck_cards (deck_id, card_id, count, is_sideboard) VALUES (?1, ?2, ?3, 0)",
-            params![deck_id, card_id, count],
+            "INSERT INTO deck_cards (deck_id, card_id, count, is_sideboard) VALUES (?1, ?2, ?3, ?4)",
+            params![deck_id, card_id, count, is_sideboard],
         )?;
     }
 
-    for (count, card) in decklist.sideboard.iter() {
-        let card_id =
-            if let Some(row) = cards_query.query(named_params! { ":name": card })?.next()? {
-                row.get(0)?
-            } else {
-                conn.execute("INSERT INTO cards (name) VALUES (?1)", params![card])?;
-                conn.last_insert_rowid()
-            };
+    Ok(true)
+}
 
-        conn.execute(
-            "INSERT INTO deck_cards (deck_id, card_id, count, is_sideboard) VALUES (?1, ?2, ?3, 1)",
-            params![deck_id, card_id, count],
-        )?;
+/// Finds the card that a site's spelling of `name` refers to, through
+/// `card_aliases` or by its normalized name. Sites often only print the
+/// front face of double-faced cards, so those match too. Creates a card
+/// without Scryfall data when nothing matches.
+pub fn resolve_card(conn: &Connection, name: &str) -> Result<i64> {
+    if let Some((card_id, _, _)) = find_card(conn, name)? {
+        return Ok(card_id);
+    }
+
+    conn.execute(
+        "INSERT INTO cards (name) VALUES (?1)",
+        [cards::normalize_name(name)],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Looks a card up the way [`resolve_card`] does without creating it.
+/// Returns its id, stored name and whether it has Scryfall data.
+pub fn find_card(conn: &Connection, name: &str) -> Result<Option<(i64, String, bool)>> {
+    let name = cards::normalize_name(name);
+    let card = |row: &Row| Ok((row.get(0)?, row.get(1)?, row.get(2)?));
+
+    let alias = conn
+        .query_row(
+            "SELECT cards.id, cards.name, cards.scryfall_id IS NOT NULL
+                FROM card_aliases
+                JOIN cards ON cards.id = card_aliases.card_id
+                WHERE card_aliases.alias = ?1",
+            [cards::name_key(&name)],
+            card,
+        )
+        .optional()?;
+
+    if alias.is_some() {
+        return Ok(alias);
     }
 
+    // Cards with Scryfall data first, then exact names before front faces
+    conn.query_row(
+        "SELECT id, name, scryfall_id IS NOT NULL FROM cards
+            WHERE name = ?1 COLLATE NOCASE
+               OR (scryfall_id IS NOT NULL
+                   AND lower(substr(name, 1, length(?1) + 4)) = lower(?1 || ' // '))
+            ORDER BY scryfall_id IS NULL, name = ?1 COLLATE NOCASE DESC, id
+            LIMIT 1",
+        [&name],
+        card,
+    )
+    .optional()
+}
+
+/// Moves everything that references card `from`, price history included,
+/// over to `into`, remembers the spelling of `from` as an alias of `into`
+/// and deletes `from`.
+pub fn merge_card(conn: &Connection, from: i64, into: i64) -> Result<()> {
+    let name: String = conn.query_row("SELECT name FROM cards WHERE id = ?1", [from], |row| {
+        row.get(0)
+    })?;
+
+    conn.execute(
+        "UPDATE deck_cards SET card_id = ?2 WHERE card_id = ?1",
+        [from, into],
+    )?;
+    conn.execute(
+        "UPDATE card_aliases SET card_id = ?2 WHERE card_id = ?1",
+        [from, into],
+    )?;
+    insert_card_alias(conn, &name, into)?;
+    // Days both cards have a price for keep the ones of `into`
+    conn.execute(
+        "UPDATE OR IGNORE card_prices SET card_id = ?2 WHERE card_id = ?1",
+        [from, into],
+    )?;
+    conn.execute("DELETE FROM card_prices WHERE card_id = ?1", [from])?;
+    conn.execute("DELETE FROM card_faces WHERE card_id = ?1", [from])?;
+    conn.execute("DELETE FROM card_legalities WHERE card_id = ?1", [from])?;
+    conn.execute("DELETE FROM cards WHERE id = ?1", [from])?;
+
     Ok(())
 }
 
+/// Merges cards that are spellings of the same card into the one with
+/// Scryfall data, or into the oldest one. Cards without Scryfall data are
+/// also merged into a double-faced card whose front face they name.
+/// Returns the names of the merged cards. Nothing is written on a dry run.
+pub fn merge_duplicate_cards(conn: &Connection, dry_run: bool) -> Result<Vec<(String, String)>> {
+    let tx = conn.unchecked_transaction()?;
+
+    let cards = tx
+        .prepare("SELECT id, name, scryfall_id IS NOT NULL FROM cards ORDER BY id")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut by_key: HashMap<String, (i64, &str, bool)> = HashMap::new();
+    let mut by_front_face: HashMap<String, (i64, &str)> = HashMap::new();
+
+    for (id, name, is_resolved) in cards.iter() {
+        let key = cards::name_key(name);
+
+        if by_key
+            .get(&key)
+            .is_none_or(|(_, _, best)| *is_resolved && !*best)
+        {
+            by_key.insert(key, (*id, name, *is_resolved));
+        }
+
+        if let (true, Some((front, _))) = (*is_resolved, name.split_once(" // ")) {
+            by_front_face
+                .entry(cards::name_key(front))
+                .or_insert((*id, name));
+        }
+    }
+
+    let mut merges = Vec::new();
+    // Cards without Scryfall data that others are merged into are spelled
+    // the way Scryfall will, so that `fetch-cards` finds them by name
+    let mut renames = HashMap::new();
+
+    for (id, name, is_resolved) in cards.iter() {
+        let key = cards::name_key(name);
+
+        let into = match by_key.get(&key) {
+            Some((best, best_name, true)) if best != id => Some((*best, best_name.to_string())),
+            Some((best, best_name, false)) if best != id => {
+                let normalized = cards::normalize_name(best_name);
+                if normalized != *best_name {
+                    renames.insert(*best, normalized.clone());
+                }
+                Some((*best, normalized))
+            }
+            // The best spelling is this one, so there's no resolved card with it
+            _ if !is_resolved => by_front_face
+                .get(&key)
+                .map(|(into, into_name)| (*into, into_name.to_string())),
+            _ => None,
+        };
+
+        if let Some((into, into_name)) = into {
+            merges.push((*id, into, name.clone(), into_name));
+        }
+    }
+
+    if !dry_run {
+        for (from, into, _, _) in merges.iter() {
+            merge_card(&tx, *from, *into)?;
+        }
+        for (card_id, name) in renames.iter() {
+            tx.execute(
+                "UPDATE cards SET name = ?2 WHERE id = ?1",
+                params![card_id, name],
+            )?;
+        }
+        tx.commit()?;
+    }
+
+    Ok(merges
+        .into_iter()
+        .map(|(_, _, from, into)| (from, into))
+        .collect())
+}
+
+/// Deletes the decks scraped from `event_link`, also when `decklists` is
+/// empty. Decks inserted before the link was recorded are matched by the
+/// format, event and date of `decklists`.
+pub fn delete_event_decklists(
+    conn: &Connection,
+    event_link: &str,
+    decklists: &[Decklist],
+) -> Result<usize> {
+    let mut deleted = delete_decks(
+        conn,
+        "SELECT id FROM decks WHERE event_link = ?1",
+        params![event_link],
+    )?;
+
+    for decklist in decklists {
+        deleted += delete_decks(
+            conn,
+            "SELECT id FROM decks
+                WHERE event_link IS NULL AND format = ?1 AND event IS ?2 AND date IS ?3",
+            params![
+                decklist.format.to_string(),
+                decklist.event,
+                decklist.date.map(|d| d.to_string()),
+            ],
+        )?;
+    }
+
+    Ok(deleted)
+}
+
+/// Deletes the decks selected by `deck_ids` together with their cards and
+/// violations.
+fn delete_decks(conn: &Connection, deck_ids: &str, params: &[&dyn ToSql]) -> Result<usize> {
+    conn.execute(
+        &format!("DELETE FROM deck_cards WHERE deck_id IN ({deck_ids})"),
+        params,
+    )?;
+    conn.execute(
+        &format!("DELETE FROM deck_violations WHERE deck_id IN ({deck_ids})"),
+        params,
+    )?;
+    conn.execute(
+        &format!("DELETE FROM decks WHERE id IN ({deck_ids})"),
+        params,
+    )
+}
+
+/// Saves the event, replaces its decks and marks its link as scraped in a
+/// single transaction, so that an interrupted or failed write never leaves a
+/// partial event behind. Returns the number of decks deleted and inserted,
+/// duplicates of decks from other events are skipped.
+pub fn save_event(conn: &Connection, scraped: &ScrapedEvent) -> Result<(usize, usize)> {
+    let event = &scraped.event;
+    let tx = conn.unchecked_transaction()?;
+
+    let replaced = replace_event_decklists(&tx, scraped)?;
+    insert_scraped_link(&tx, &event.source, &event.format, &event.url, None)?;
+
+    tx.commit()?;
+
+    Ok(replaced)
+}
+
+/// Upserts the event and replaces its decks with the scraped ones. Returns
+/// the number of decks deleted and inserted.
+pub fn replace_event_decklists(
+    conn: &Connection,
+    scraped: &ScrapedEvent,
+) -> Result<(usize, usize)> {
+    let event = &scraped.event;
+    let event_id = upsert_event(conn, event)?;
+
+    let deleted = delete_event_decklists(conn, &event.url, &scraped.decklists)?;
+    let mut inserted = 0;
+
+    for decklist in scraped.decklists.iter() {
+        if insert_decklist(conn, event_id, &event.url, decklist)? {
+            inserted += 1;
+        }
+    }
+
+    Ok((deleted, inserted))
+}
+
+/// Inserts or updates the event with the same url, returning its id.
+pub fn upsert_event(conn: &Connection, event: &Event) -> Result<i64> {
+    conn.query_row(
+        "INSERT INTO events (source, url, name, format, date, event_type, players)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(url) DO UPDATE SET
+                source     = excluded.source,
+                name       = excluded.name,
+                format     = excluded.format,
+                date       = excluded.date,
+                event_type = excluded.event_type,
+                players    = excluded.players
+            RETURNING id",
+        params![
+            event.source,
+            event.url,
+            event.name,
+            event.format.to_string(),
+            event.date.map(|d| d.to_string()),
+            event.event_type,
+            event.players,
+        ],
+        |row| row.get(0),
+    )
+}
+
 pub fn insert_scraped_link(
     conn: &Connection,
+    source: &str,
+    format: &Format,
     link: &str,
-    is_success: bool,
-    error_msg: Option<&str>,
+    failure: Option<(FailureKind, &str)>,
 ) -> Result<usize> {
-    match error_msg {
-        Some(msg) => conn.execute(
-            "INSERT INTO scraped_links (link, is_success, error_msg) VALUES (?1, ?2, ?3)",
-            params![link, is_success, msg],
-        ),
-        None => conn.execute(
-            "INSERT INTO scraped_links (link, is_success) VALUES (?1, ?2)",
-            params![link, is_success],
-        ),
-    }
+    conn.execute(
+        "INSERT INTO scraped_links (
+                link,
+                source,
+                format,
+                is_success,
+                error_kind,
+                error_msg,
+                attempts,
+                last_attempt_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, CURRENT_TIMESTAMP)
+            ON CONFLICT(link) DO UPDATE SET
+                source          = excluded.source,
+                format          = excluded.format,
+                is_success      = excluded.is_success,
+                error_kind      = excluded.error_kind,
+                error_msg       = excluded.error_msg,
+                attempts        = scraped_links.attempts + 1,
+                last_attempt_at = excluded.last_attempt_at",
+        params![
+            link,
+            source,
+            format.to_string(),
+            failure.is_none(),
+            failure.map(|(kind, _)| kind.to_string()),
+            failure.map(|(_, msg)| msg),
+        ],
+    )
+}
+
+const SCRAPED_LINK_COLUMNS: &str = "id, link, source, format, is_success, error_kind, error_msg,
+    attempts, last_attempt_at, created_at";
+
+fn scraped_link_from_row(row: &Row) -> Result<ScrapedLink> {
+    let format: Option<String> = row.get(3)?;
+    let error_kind: Option<String> = row.get(5)?;
+    let last_attempt_at: Option<String> = row.get(8)?;
+
+    Ok(ScrapedLink {
+        id: row.get(0)?,
+        link: row.get(1)?,
+        source: row.get(2)?,
+        format: format.map(|f| f.as_str().into()),
+        is_success: row.get(4)?,
+        error_kind: error_kind.map(|k| k.as_str().into()),
+        error_msg: row.get(6)?,
+        attempts: row.get(7)?,
+        last_attempt_at: last_attempt_at
+            .and_then(|t| NaiveDateTime::parse_from_str(&t, "%Y-%m-%d %H:%M:%S").ok()),
+        created_at: row.get(9)?,
+    })
 }
 
 pub fn find_scraped_link(conn: &Connection, link: &str) -> Result<Option<ScrapedLink>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, link, is_success, error_msg, created_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SCRAPED_LINK_COLUMNS}
             FROM scraped_links
-            WHERE link = ?1",
+            WHERE link = ?1"
+    ))?;
+    stmt.query_row([link], scraped_link_from_row).optional()
+}
+
+pub fn find_scraped_links(conn: &Connection, source: &str) -> Result<Vec<ScrapedLink>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SCRAPED_LINK_COLUMNS}
+            FROM scraped_links
+            WHERE source = ?1
+            ORDER BY id"
+    ))?;
+    let rows = stmt.query_map([source], scraped_link_from_row)?;
+
+    rows.collect()
+}
+
+pub fn find_failed_links(conn: &Connection, source: &str) -> Result<Vec<ScrapedLink>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SCRAPED_LINK_COLUMNS}
+            FROM scraped_links
+            WHERE NOT is_success AND source = ?1
+            ORDER BY last_attempt_at"
+    ))?;
+    let rows = stmt.query_map([source], scraped_link_from_row)?;
+
+    rows.collect()
+}
+
+/// Failed links that [`find_failed_links`] can't return for any source.
+pub fn count_failed_links_without_source(conn: &Connection) -> Result<usize> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM scraped_links WHERE NOT is_success AND source IS NULL",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// `body` is expected to be gzipped already, see [`crate::archive::compress`].
+pub fn insert_raw_page(conn: &Connection, source: &str, url: &str, body: &[u8]) -> Result<usize> {
+    conn.execute(
+        "INSERT INTO raw_pages (source, url, body) VALUES (?1, ?2, ?3)",
+        params![source, url, body],
+    )
+}
+
+/// Returns the gzipped body of the most recent copy of `url`.
+pub fn find_raw_page(conn: &Connection, url: &str) -> Result<Option<Vec<u8>>> {
+    conn.query_row(
+        "SELECT body FROM raw_pages WHERE url = ?1 ORDER BY fetched_at DESC, id DESC LIMIT 1",
+        [url],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn find_decklists(conn: &Connection, filter: &DecklistFilter) -> Result<Vec<Decklist>> {
+    Ok(find_decks(conn, filter)?
+        .into_iter()
+        .map(|(_, decklist)| decklist)
+        .collect())
+}
+
+/// Like [`find_decklists`], with the id of every deck.
+pub fn find_decks(conn: &Connection, filter: &DecklistFilter) -> Result<Vec<(i64, Decklist)>> {
+    let mut decks_query = conn.prepare(
+        "SELECT id, format, event, date, player, archetype, result, name, source, source_url,
+                external_id
+            FROM decks
+            WHERE (?1 IS NULL OR date >= ?1)
+              AND (?2 IS NULL OR date <= ?2)
+            ORDER BY date, id",
+    )?;
+
+    let mut cards_query = conn.prepare(
+        "SELECT deck_cards.count, cards.name, deck_cards.is_sideboard
+            FROM deck_cards
+            JOIN cards ON cards.id = deck_cards.card_id
+            WHERE deck_cards.deck_id = ?1",
+    )?;
+
+    let decks = decks_query.query_map(
+        params![
+            filter.since.map(|d| d.to_string()),
+            filter.until.map(|d| d.to_string())
+        ],
+        |row| {
+            let format: String = row.get(1)?;
+            let date: Option<String> = row.get(3)?;
+
+            Ok((
+                row.get::<_, i64>(0)?,
+                Decklist {
+                    format: format.as_str().into(),
+                    event: row.get(2)?,
+                    date: date.and_then(|d| d.parse().ok()),
+                    player: row.get(4)?,
+                    archetype: row.get(5)?,
+                    result: row.get(6)?,
+                    name: row.get(7)?,
+                    source: row.get(8)?,
+                    source_url: row.get(9)?,
+                    external_id: row.get(10)?,
+                    mainboard: Vec::new(),
+                    sideboard: Vec::new(),
+                },
+            ))
+        },
     )?;
-    stmt.query_row([link], |row| {
-        Ok(ScrapedLink {
-            id: row.get(0)?,
-            link: row.get(1)?,
-            is_success: row.get(2)?,
-            error_msg: row.get(3)?,
-            created_at: row.get(4)?,
-        })
+
+    let mut decklists = Vec::new();
+
+    for deck in decks {
+        let (deck_id, mut decklist) = deck?;
+
+        if !filter.matches_format(&decklist.format) {
+            continue;
+        }
+
+        let cards = cards_query.query_map([deck_id], |row| {
+            Ok((
+                row.get::<_, usize>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+            ))
+        })?;
+
+        for card in cards {
+            let (count, name, is_sideboard) = card?;
+
+            if is_sideboard {
+                decklist.sideboard.push((count, name));
+            } else {
+                decklist.mainboard.push((count, name));
+            }
+        }
+
+        decklists.push((deck_id, decklist));
+    }
+
+    Ok(decklists)
+}
+
+/// Deletes all but the oldest of the decks with the same fingerprint and
+/// brings the stored fingerprints up to date. Returns the number of decks
+/// deleted and fingerprints updated. Nothing is written on a dry run.
+pub fn dedupe_decks(conn: &Connection, dry_run: bool) -> Result<(usize, usize)> {
+    let tx = conn.unchecked_transaction()?;
+
+    let stored = tx
+        .prepare("SELECT id, fingerprint FROM decks")?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut outdated = Vec::new();
+
+    let mut decks = find_decks(&tx, &DecklistFilter::default())?;
+    // Oldest first, so the first deck with a fingerprint is the one kept
+    decks.sort_by_key(|(deck_id, _)| *deck_id);
+
+    for (deck_id, decklist) in decks {
+        let fingerprint = decklist.fingerprint();
+
+        if !seen.insert(fingerprint.clone()) {
+            duplicates.push(deck_id);
+        } else if stored.get(&deck_id) != Some(&Some(fingerprint.clone())) {
+            outdated.push((deck_id, fingerprint));
+        }
+    }
+
+    if dry_run {
+        return Ok((duplicates.len(), outdated.len()));
+    }
+
+    for deck_id in duplicates.iter() {
+        tx.execute("DELETE FROM deck_cards WHERE deck_id = ?1", [deck_id])?;
+        tx.execute("DELETE FROM deck_violations WHERE deck_id = ?1", [deck_id])?;
+        tx.execute("DELETE FROM decks WHERE id = ?1", [deck_id])?;
+    }
+
+    // Cleared first, an outdated fingerprint may belong to another deck now
+    for (deck_id, _) in outdated.iter() {
+        tx.execute(
+            "UPDATE decks SET fingerprint = NULL WHERE id = ?1",
+            [deck_id],
+        )?;
+    }
+    for (deck_id, fingerprint) in outdated.iter() {
+        tx.execute(
+            "UPDATE decks SET fingerprint = ?1 WHERE id = ?2",
+            params![fingerprint, deck_id],
+        )?;
+    }
+
+    tx.commit()?;
+
+    Ok((duplicates.len(), outdated.len()))
+}
+
+pub fn count_decks_by_format(conn: &Connection) -> Result<Vec<(String, usize)>> {
+    let mut stmt =
+        conn.prepare("SELECT format, COUNT(*) FROM decks GROUP BY format ORDER BY COUNT(*) DESC")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    rows.collect()
+}
+
+/// Legalities in `format` and the basic lands, by card name.
+pub fn find_card_data(conn: &Connection, format: &Format) -> Result<CardData> {
+    let legalities = conn
+        .prepare(
+            "SELECT cards.name, card_legalities.legality
+                FROM card_legalities
+                JOIN cards ON cards.id = card_legalities.card_id
+                WHERE card_legalities.format = ?1",
+        )?
+        .query_map([format.to_string()], |row| {
+            let legality: String = row.get(1)?;
+            Ok((row.get::<_, String>(0)?, legality.as_str().into()))
+        })?
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let basic_lands = conn
+        .prepare("SELECT name FROM cards WHERE type_line LIKE 'Basic %'")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<HashSet<_>>>()?;
+
+    Ok(CardData {
+        legalities,
+        basic_lands,
     })
+}
+
+pub fn replace_deck_violations(
+    conn: &Connection,
+    deck_id: i64,
+    violations: &[Violation],
+) -> Result<()> {
+    conn.execute("DELETE FROM deck_violations WHERE deck_id = ?1", [deck_id])?;
+
+    for violation in violations.iter() {
+        conn.execute(
+            "INSERT INTO deck_violations (deck_id, kind, card, message) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                deck_id,
+                violation.kind(),
+                violation.card(),
+                violation.to_string()
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn count_events(conn: &Connection) -> Result<usize> {
+    conn.query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))
+}
+
+/// Cards without Scryfall data with the number of decks using them, most
+/// used first.
+pub fn find_unresolved_cards(conn: &Connection) -> Result<Vec<(i64, String, usize)>> {
+    let mut stmt = conn.prepare(
+        "SELECT cards.id, cards.name, COUNT(DISTINCT deck_cards.deck_id) AS decks
+            FROM cards
+            LEFT JOIN deck_cards ON deck_cards.card_id = cards.id
+            WHERE cards.scryfall_id IS NULL
+            GROUP BY cards.id
+            ORDER BY decks DESC, cards.name",
+    )?;
+
+    let cards = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(cards)
+}
+
+/// Finds a card without Scryfall data by its stored name, the one
+/// [`find_unresolved_cards`] lists. Unlike [`find_card`] the name isn't
+/// normalized, a legacy spelling like `Lightning  Bolt` is found as is.
+pub fn find_unresolved_card(conn: &Connection, name: &str) -> Result<Option<(i64, String)>> {
+    conn.query_row(
+        "SELECT id, name FROM cards
+            WHERE name = ?1 COLLATE NOCASE AND scryfall_id IS NULL
+            ORDER BY name = ?1 DESC, id
+            LIMIT 1",
+        [name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
     .optional()
 }
+
+pub fn find_resolved_cards(conn: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut stmt = conn.prepare("SELECT id, name FROM cards WHERE scryfall_id IS NOT NULL")?;
+
+    let cards = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(cards)
+}
+
+pub fn count_cards(conn: &Connection) -> Result<(usize, usize)> {
+    conn.query_row(
+        "SELECT COUNT(*), COUNT(*) FILTER (WHERE scryfall_id IS NULL) FROM cards",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+pub fn count_scraped_links(conn: &Connection) -> Result<(usize, usize)> {
+    conn.query_row(
+        "SELECT COUNT(*) FILTER (WHERE is_success), COUNT(*) FILTER (WHERE NOT is_success)
+            FROM scraped_links",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scraped_event(decklists: Vec<Decklist>) -> ScrapedEvent {
+        ScrapedEvent {
+            event: Event {
+                source: "tcdecks".to_owned(),
+                url: "deck.php?id=1".to_owned(),
+                name: Some("Test Event".to_owned()),
+                format: Format::Premodern,
+                date: NaiveDate::from_ymd_opt(2024, 5, 1),
+                event_type: None,
+                players: None,
+            },
+            decklists,
+        }
+    }
+
+    fn decklist(player: &str) -> Decklist {
+        Decklist {
+            format: Format::Premodern,
+            player: Some(player.to_owned()),
+            event: Some("Test Event".to_owned()),
+            date: NaiveDate::from_ymd_opt(2024, 5, 1),
+            mainboard: vec![
+                (4, "Lightning Bolt".to_owned()),
+                (56, "Mountain".to_owned()),
+            ],
+            sideboard: vec![],
+            archetype: None,
+            result: None,
+            name: None,
+            source: Some("tcdecks".to_owned()),
+            source_url: None,
+            external_id: None,
+        }
+    }
+
+    #[test]
+    fn replacing_with_no_decks_deletes_the_old_ones() {
+        let conn = Connection::open_in_memory().unwrap();
+        setup(&conn).unwrap();
+
+        save_event(&conn, &scraped_event(vec![decklist("A"), decklist("B")])).unwrap();
+        let replaced = replace_event_decklists(&conn, &scraped_event(vec![])).unwrap();
+
+        assert_eq!(replaced, (2, 0));
+        assert!(find_decks(&conn, &DecklistFilter::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn merging_unresolved_spellings_keeps_the_normalized_name() {
+        let conn = Connection::open_in_memory().unwrap();
+        setup(&conn).unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO cards (id, name) VALUES (1, 'Fire/Ice'), (2, 'fire / ice');
+            INSERT INTO cards (id, name) VALUES (3, 'Lightning Bolt'), (4, 'Brainstorm');
+            UPDATE cards SET scryfall_id = 'bolt' WHERE id = 3;
+            INSERT INTO cards (id, name) VALUES (5, 'lightning  bolt');",
+        )
+        .unwrap();
+
+        let mut merges = merge_duplicate_cards(&conn, true).unwrap();
+        merges.sort();
+        assert_eq!(
+            merges,
+            vec![
+                ("fire / ice".to_owned(), "Fire // Ice".to_owned()),
+                ("lightning  bolt".to_owned(), "Lightning Bolt".to_owned()),
+            ]
+        );
+        assert_eq!(count_cards(&conn).unwrap(), (5, 4));
+
+        merge_duplicate_cards(&conn, false).unwrap();
+
+        assert_eq!(count_cards(&conn).unwrap(), (3, 2));
+        assert_eq!(
+            find_card(&conn, "fire/ice").unwrap(),
+            Some((1, "Fire // Ice".to_owned(), false))
+        );
+        assert_eq!(find_card(&conn, "Lightning  Bolt").unwrap().unwrap().0, 3);
+    }
+
+    #[test]
+    fn decks_are_priced_with_the_latest_price_on_or_before_the_date() {
+        let conn = Connection::open_in_memory().unwrap();
+        setup(&conn).unwrap();
+
+        save_event(&conn, &scraped_event(vec![decklist("A")])).unwrap();
+        let (deck_id, _) = find_decks(&conn, &DecklistFilter::default())
+            .unwrap()
+            .remove(0);
+        let bolt = find_card(&conn, "Lightning Bolt").unwrap().unwrap().0;
+
+        // Mountain has no prices at all, tix is only known from the first day
+        conn.execute(
+            "INSERT INTO card_prices (card_id, date, usd, eur, tix) VALUES
+                (?1, '2024-05-01', 1.0, NULL, 0.25),
+                (?1, '2024-05-03', 2.0, 1.5, NULL),
+                (?1, '2024-05-10', 5.0, 4.0, 1.0)",
+            [bolt],
+        )
+        .unwrap();
+
+        let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+        let totals = |price: DeckPrice| {
+            [price.usd, price.eur, price.tix].map(|total| (total.total, total.missing))
+        };
+
+        assert_eq!(
+            totals(find_deck_price(&conn, deck_id, date(5)).unwrap()),
+            [(8.0, 56), (6.0, 56), (1.0, 56)]
+        );
+        assert_eq!(
+            totals(find_deck_price(&conn, deck_id, date(1)).unwrap()),
+            [(4.0, 56), (0.0, 60), (1.0, 56)]
+        );
+        assert_eq!(
+            totals(
+                find_deck_price(
+                    &conn,
+                    deck_id,
+                    NaiveDate::from_ymd_opt(2024, 4, 30).unwrap()
+                )
+                .unwrap()
+            ),
+            [(0.0, 60), (0.0, 60), (0.0, 60)]
+        );
+    }
+}
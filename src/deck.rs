@@ -1,9 +1,14 @@
 use chrono::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
 use std::fmt;
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+use crate::cards;
+use crate::hash;
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Format {
     Standard,
     Pioneer,
@@ -16,6 +21,7 @@ pub enum Format {
     Explorer,
     Premodern,
     OldSchool,
+    Commander,
     Unknown,
 }
 
@@ -33,6 +39,7 @@ impl From<&str> for Format {
             "explorer" => Format::Explorer,
             "oldschool" => Format::OldSchool,
             "premodern" => Format::Premodern,
+            "commander" => Format::Commander,
             _ => Format::Unknown,
         }
     }
@@ -52,6 +59,7 @@ impl fmt::Display for Format {
             Format::Explorer => write!(f, "explorer"),
             Format::Premodern => write!(f, "premodern"),
             Format::OldSchool => write!(f, "oldschool"),
+            Format::Commander => write!(f, "commander"),
             Format::Unknown => write!(f, "unknown"),
         }
     }
@@ -66,7 +74,27 @@ pub struct DecklistLinks {
     _display_see_more: i32,
 }
 
+/// An event as published by a source. `url` is the link the event was
+/// scraped from, the same one that is kept in `scraped_links`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub source: String,
+    pub url: String,
+    pub name: Option<String>,
+    pub format: Format,
+    pub date: Option<NaiveDate>,
+    pub event_type: Option<String>,
+    pub players: Option<u32>,
+}
+
+/// Everything scraped from a single event link.
 #[derive(Debug)]
+pub struct ScrapedEvent {
+    pub event: Event,
+    pub decklists: Vec<Decklist>,
+}
+
+#[derive(Debug, Serialize)]
 pub struct Decklist {
     pub format: Format,
     pub player: Option<String>,
@@ -77,13 +105,229 @@ pub struct Decklist {
     pub archetype: Option<String>,
     pub result: Option<String>,
     pub name: Option<String>,
+    /// Name of the scraper that produced the deck
+    pub source: Option<String>,
+    /// Page the deck was parsed from
+    pub source_url: Option<String>,
+    /// The source's own id for the deck, when it has one
+    pub external_id: Option<String>,
+}
+
+impl Decklist {
+    /// Identifies the same deck scraped twice, e.g. from two pages of the
+    /// same event. Hashes the format, player, event, date and the card counts
+    /// sorted by name, ignoring case and extra whitespace. Cards are hashed
+    /// by the [`cards::name_key`] of their front face, so that the spelling
+    /// of a site and the name stored in `cards`, e.g. `fire/ice` and
+    /// `Fire // Ice` or `Delver of Secrets` and `Delver of Secrets //
+    /// Insectile Aberration`, give the same fingerprint.
+    pub fn fingerprint(&self) -> String {
+        let normalize = |value: &Option<String>| {
+            value
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        };
+
+        let cards = |cards: &[(usize, String)]| {
+            let mut counts = BTreeMap::new();
+            for (count, name) in cards.iter() {
+                let key = cards::name_key(name);
+                let front = key.split(" // ").next().unwrap_or_default().to_owned();
+                *counts.entry(front).or_insert(0) += count;
+            }
+
+            counts
+                .iter()
+                .map(|(name, count)| format!("{} {}", count, name))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let canonical = [
+            self.format.to_string(),
+            normalize(&self.player),
+            normalize(&self.event),
+            self.date.map(|d| d.to_string()).unwrap_or_default(),
+            cards(&self.mainboard),
+            cards(&self.sideboard),
+        ]
+        .join("\x1e");
+
+        format!("{:032x}", hash::fnv1a_128(canonical.as_bytes()))
+    }
+}
+
+/// Sum of the prices of a deck's cards in one currency.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct PriceTotal {
+    pub total: f64,
+    /// Copies of cards that had no price, left out of `total`
+    pub missing: usize,
+}
+
+/// The paper (`usd`, `eur`) and MTGO (`tix`) price of a deck.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct DeckPrice {
+    pub usd: PriceTotal,
+    pub eur: PriceTotal,
+    pub tix: PriceTotal,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DecklistFilter {
+    pub formats: Vec<Format>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl DecklistFilter {
+    pub fn matches_format(&self, format: &Format) -> bool {
+        self.formats.is_empty() || self.formats.contains(format)
+    }
+
+    pub fn matches_date(&self, date: Option<NaiveDate>) -> bool {
+        match date {
+            Some(date) => {
+                self.since.is_none_or(|since| date >= since)
+                    && self.until.is_none_or(|until| date <= until)
+            }
+            None => self.since.is_none() && self.until.is_none(),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum FailureKind {
+    /// Network errors and non-success HTTP responses, worth retrying soon
+    Transient,
+    /// The page was fetched but didn't look like we expected
+    Parse,
+    /// The page exists but has no decklists (yet)
+    NotFound,
+}
+
+impl From<&str> for FailureKind {
+    fn from(i: &str) -> Self {
+        match i {
+            "transient" => FailureKind::Transient,
+            "not_found" => FailureKind::NotFound,
+            _ => FailureKind::Parse,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureKind::Transient => write!(f, "transient"),
+            FailureKind::Parse => write!(f, "parse"),
+            FailureKind::NotFound => write!(f, "not_found"),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Legality {
+    Legal,
+    NotLegal,
+    /// Allowed as a single copy
+    Restricted,
+    Banned,
+}
+
+impl From<&str> for Legality {
+    fn from(i: &str) -> Self {
+        match i {
+            "legal" => Legality::Legal,
+            "restricted" => Legality::Restricted,
+            "banned" => Legality::Banned,
+            _ => Legality::NotLegal,
+        }
+    }
+}
+
+impl From<&scryfall::card::Legality> for Legality {
+    fn from(legality: &scryfall::card::Legality) -> Self {
+        match legality {
+            scryfall::card::Legality::Legal => Legality::Legal,
+            scryfall::card::Legality::NotLegal => Legality::NotLegal,
+            scryfall::card::Legality::Restricted => Legality::Restricted,
+            scryfall::card::Legality::Banned => Legality::Banned,
+        }
+    }
+}
+
+impl fmt::Display for Legality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Legality::Legal => write!(f, "legal"),
+            Legality::NotLegal => write!(f, "not_legal"),
+            Legality::Restricted => write!(f, "restricted"),
+            Legality::Banned => write!(f, "banned"),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct ScrapedLink {
     pub id: i32,
     pub link: String,
+    pub source: Option<String>,
+    pub format: Option<Format>,
     pub is_success: bool,
+    pub error_kind: Option<FailureKind>,
     pub error_msg: Option<String>,
+    pub attempts: u32,
+    pub last_attempt_at: Option<NaiveDateTime>,
     pub created_at: String,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decklist(mainboard: &[(usize, &str)]) -> Decklist {
+        Decklist {
+            format: Format::Legacy,
+            player: Some("Alice".to_owned()),
+            event: Some("Legacy Challenge".to_owned()),
+            date: NaiveDate::from_ymd_opt(2024, 5, 5),
+            mainboard: mainboard
+                .iter()
+                .map(|(count, name)| (*count, name.to_string()))
+                .collect(),
+            sideboard: vec![],
+            archetype: None,
+            result: None,
+            name: None,
+            source: None,
+            source_url: None,
+            external_id: None,
+        }
+    }
+
+    #[test]
+    fn fingerprint_matches_the_stored_card_names() {
+        let scraped = decklist(&[(2, "fire/ice"), (4, "Æther Vial"), (4, "Delver of Secrets")]);
+        let stored = decklist(&[
+            (4, "Delver of Secrets // Insectile Aberration"),
+            (4, "Aether Vial"),
+            (2, "Fire // Ice"),
+        ]);
+
+        assert_eq!(scraped.fingerprint(), stored.fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_depends_on_the_card_counts() {
+        assert_ne!(
+            decklist(&[(4, "Brainstorm")]).fingerprint(),
+            decklist(&[(3, "Brainstorm")]).fingerprint()
+        );
+    }
+}
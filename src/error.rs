@@ -0,0 +1,94 @@
+use reqwest::StatusCode;
+use std::fmt;
+
+use crate::deck::FailureKind;
+
+/// Everything that can go wrong while fetching and parsing a page. The
+/// `Display` output ends up in `scraped_links.error_msg`.
+#[derive(Debug)]
+pub enum ScrapeError {
+    Http(reqwest::Error),
+    Url(url::ParseError),
+    Json(serde_json::Error),
+    MissingElement { selector: String, url: String },
+    BadDate { value: String, url: String },
+    BadCount { value: String, url: String },
+    NotFound { url: String },
+    NotArchived { url: String },
+    Disallowed { url: String },
+}
+
+impl ScrapeError {
+    pub fn missing(selector: &str, url: &str) -> Self {
+        ScrapeError::MissingElement {
+            selector: selector.to_owned(),
+            url: url.to_owned(),
+        }
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            ScrapeError::Http(e) if e.status() == Some(StatusCode::NOT_FOUND) => {
+                FailureKind::NotFound
+            }
+            ScrapeError::Http(_) => FailureKind::Transient,
+            ScrapeError::NotFound { .. }
+            | ScrapeError::NotArchived { .. }
+            | ScrapeError::Disallowed { .. } => FailureKind::NotFound,
+            ScrapeError::Url(_)
+            | ScrapeError::Json(_)
+            | ScrapeError::MissingElement { .. }
+            | ScrapeError::BadDate { .. }
+            | ScrapeError::BadCount { .. } => FailureKind::Parse,
+        }
+    }
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScrapeError::Http(e) => write!(f, "http error: {}", e),
+            ScrapeError::Url(e) => write!(f, "invalid url: {}", e),
+            ScrapeError::Json(e) => write!(f, "invalid json: {}", e),
+            ScrapeError::MissingElement { selector, url } => {
+                write!(f, "missing element `{}` on {}", selector, url)
+            }
+            ScrapeError::BadDate { value, url } => write!(f, "bad date {:?} on {}", value, url),
+            ScrapeError::BadCount { value, url } => {
+                write!(f, "bad card count {:?} on {}", value, url)
+            }
+            ScrapeError::NotFound { url } => write!(f, "decklists not found on {}", url),
+            ScrapeError::NotArchived { url } => write!(f, "no archived copy of {}", url),
+            ScrapeError::Disallowed { url } => write!(f, "{} is disallowed by robots.txt", url),
+        }
+    }
+}
+
+impl std::error::Error for ScrapeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScrapeError::Http(e) => Some(e),
+            ScrapeError::Url(e) => Some(e),
+            ScrapeError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ScrapeError {
+    fn from(e: reqwest::Error) -> Self {
+        ScrapeError::Http(e)
+    }
+}
+
+impl From<url::ParseError> for ScrapeError {
+    fn from(e: url::ParseError) -> Self {
+        ScrapeError::Url(e)
+    }
+}
+
+impl From<serde_json::Error> for ScrapeError {
+    fn from(e: serde_json::Error) -> Self {
+        ScrapeError::Json(e)
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::prelude::{DateTime, Utc};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{StatusCode, Url};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::archive::Archive;
+use crate::config::SourceConfig;
+use crate::error::ScrapeError;
+use crate::hash;
+use crate::ratelimit::RateLimiter;
+use crate::robots::Robots;
+
+/// Sent when no `user_agent` is configured, also used to pick the robots.txt
+/// rules that apply to us.
+const DEFAULT_USER_AGENT: &str = "deck-list-scraper";
+const MAX_RETRIES: u32 = 3;
+/// Wait after a 429 or 503 without `Retry-After`, doubled on every retry
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Where the pages of a [`Fetcher`] come from. Transports are shared by the
+/// scraper threads of a source.
+pub trait Transport: Send + Sync {
+    fn get(&self, url: &Url) -> Result<String, ScrapeError>;
+}
+
+/// Fetches pages for a source and keeps a copy of every successful response
+/// in the raw page archive, when one is configured.
+pub struct Fetcher {
+    source: String,
+    transport: Box<dyn Transport>,
+    archive: Option<Archive>,
+}
+
+impl Fetcher {
+    pub fn new(source: &str, transport: Box<dyn Transport>) -> Self {
+        Fetcher {
+            source: source.to_owned(),
+            transport,
+            archive: None,
+        }
+    }
+
+    /// Serves pages from the archive and never touches the network.
+    pub fn offline(source: &str, archive: Archive) -> Self {
+        Fetcher::new(source, Box::new(ArchiveTransport { archive }))
+    }
+
+    pub fn with_archive(mut self, archive: Archive) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    pub fn get(&self, url: &Url) -> Result<String, ScrapeError> {
+        let body = self.transport.get(url)?;
+
+        if let Some(archive) = &self.archive {
+            if let Err(e) = archive.store(&self.source, url.as_str(), &body) {
+                eprintln!("Failed to archive {}: {}", url, e);
+            }
+        }
+
+        Ok(body)
+    }
+}
+
+/// Fetches pages over the network. Every request waits for its turn in the
+/// shared [`RateLimiter`] and is checked against the site's robots.txt.
+pub struct HttpTransport {
+    client: Client,
+    limiter: Arc<RateLimiter>,
+    config: SourceConfig,
+    robots: OnceLock<Robots>,
+}
+
+impl HttpTransport {
+    /// Fails when the client can't be built from `config`, e.g. for a
+    /// `user_agent` that isn't a valid header value.
+    pub fn new(config: &SourceConfig, limiter: Arc<RateLimiter>) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(config.timeout())
+            .user_agent(user_agent(config))
+            .build()?;
+
+        if let Some(host) = Url::parse(&config.base_url)
+            .ok()
+            .as_ref()
+            .and_then(Url::host_str)
+        {
+            limiter.configure(host, config.delay(), config.burst);
+        }
+
+        Ok(HttpTransport {
+            client,
+            limiter,
+            config: config.clone(),
+            robots: OnceLock::new(),
+        })
+    }
+
+    fn robots(&self) -> &Robots {
+        self.robots.get_or_init(|| {
+            let contents = match &self.config.robots_txt {
+                Some(path) => fs::read_to_string(path)
+                    .map_err(|e| eprintln!("Failed to read {}: {}", path.display(), e))
+                    .ok(),
+                None => Url::parse(&self.config.base_url)
+                    .and_then(|url| url.join("/robots.txt"))
+                    .ok()
+                    .and_then(|url| self.send(&url).ok()),
+            };
+
+            let robots = contents
+                .map(|contents| Robots::parse(&contents, user_agent(&self.config)))
+                .unwrap_or_default();
+
+            if let (Some(crawl_delay), Some(host)) = (
+                robots.crawl_delay,
+                Url::parse(&self.config.base_url)
+                    .ok()
+                    .as_ref()
+                    .and_then(Url::host_str),
+            ) {
+                self.limiter.configure(host, crawl_delay, self.config.burst);
+            }
+
+            robots
+        })
+    }
+
+    /// Sends a rate limited request, waiting and retrying when the host asks
+    /// us to slow down.
+    fn send(&self, url: &Url) -> Result<String, ScrapeError> {
+        let host = url.host_str().unwrap_or_default();
+        let mut attempt = 0;
+
+        loop {
+            self.limiter.acquire(host);
+
+            let res = self.client.get(url.clone()).send()?;
+            let status = res.status();
+
+            if (status == StatusCode::TOO_MANY_REQUESTS
+                || status == StatusCode::SERVICE_UNAVAILABLE)
+                && attempt < MAX_RETRIES
+            {
+                let wait = retry_after(res.headers())
+                    .unwrap_or_else(|| DEFAULT_RETRY_AFTER * 2u32.pow(attempt));
+
+                eprintln!("{} answered {}, waiting {:?}", host, status, wait);
+                self.limiter.block(host, wait);
+                attempt += 1;
+                continue;
+            }
+
+            return Ok(res.error_for_status()?.text()?);
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, url: &Url) -> Result<String, ScrapeError> {
+        if self.config.respect_robots {
+            let path = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_owned(),
+            };
+
+            if !self.robots().is_allowed(&path) {
+                return Err(ScrapeError::Disallowed {
+                    url: url.to_string(),
+                });
+            }
+        }
+
+        self.send(url)
+    }
+}
+
+fn user_agent(config: &SourceConfig) -> &str {
+    config.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT)
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .and_then(|date| (date.with_timezone(&Utc) - Utc::now()).to_std().ok())
+}
+
+pub struct ArchiveTransport {
+    archive: Archive,
+}
+
+impl Transport for ArchiveTransport {
+    fn get(&self, url: &Url) -> Result<String, ScrapeError> {
+        match self.archive.load(url.as_str()) {
+            Ok(Some(body)) => Ok(body),
+            Ok(None) => Err(ScrapeError::NotArchived {
+                url: url.to_string(),
+            }),
+            Err(e) => {
+                eprintln!("Failed to read archived {}: {}", url, e);
+                Err(ScrapeError::NotArchived {
+                    url: url.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Passes requests through to `inner` and saves every response to a fixture
+/// directory that [`ReplayTransport`] can serve later.
+pub struct RecordTransport {
+    inner: Box<dyn Transport>,
+    dir: PathBuf,
+}
+
+impl RecordTransport {
+    pub fn new(inner: Box<dyn Transport>, dir: &Path) -> Self {
+        RecordTransport {
+            inner,
+            dir: dir.to_owned(),
+        }
+    }
+}
+
+impl Transport for RecordTransport {
+    fn get(&self, url: &Url) -> Result<String, ScrapeError> {
+        let body = self.inner.get(url)?;
+        let path = fixture_path(&self.dir, url);
+
+        if let Err(e) = fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, &body)) {
+            eprintln!("Failed to record {} to {}: {}", url, path.display(), e);
+        }
+
+        Ok(body)
+    }
+}
+
+/// Serves responses saved by [`RecordTransport`], fails on anything that
+/// wasn't recorded.
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    pub fn new(dir: &Path) -> Self {
+        ReplayTransport {
+            dir: dir.to_owned(),
+        }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn get(&self, url: &Url) -> Result<String, ScrapeError> {
+        let path = fixture_path(&self.dir, url);
+
+        match fs::read_to_string(&path) {
+            Ok(body) => Ok(body),
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    eprintln!("Failed to read {}: {}", path.display(), e);
+                }
+                Err(ScrapeError::NotArchived {
+                    url: url.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Fixture file names are the host followed by a stable hash of the whole
+/// url, query strings make urls too long to use as they are.
+fn fixture_path(dir: &Path, url: &Url) -> PathBuf {
+    let hash = hash::fnv1a_64(url.as_str().as_bytes());
+
+    dir.join(format!(
+        "{}-{:016x}.html",
+        url.host_str().unwrap_or("unknown"),
+        hash
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        assert_eq!(
+            retry_after(&headers(" 120 ")),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn retry_after_as_date() {
+        let date = (Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let wait = retry_after(&headers(&date)).unwrap();
+
+        assert!(wait > Duration::from_secs(80) && wait <= Duration::from_secs(90));
+    }
+
+    #[test]
+    fn retry_after_in_the_past_or_invalid() {
+        assert_eq!(retry_after(&headers("Wed, 21 Oct 2015 07:28:00 GMT")), None);
+        assert_eq!(retry_after(&headers("-5")), None);
+        assert_eq!(retry_after(&headers("later")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn invalid_user_agent_is_an_error() {
+        let config = SourceConfig {
+            user_agent: Some("deck-list-scraper\n".to_owned()),
+            ..Default::default()
+        };
+
+        assert!(HttpTransport::new(&config, Arc::new(RateLimiter::new())).is_err());
+    }
+}
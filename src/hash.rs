@@ -0,0 +1,15 @@
+//! FNV-1a hashes. Unlike `DefaultHasher` they stay the same across Rust
+//! releases, so their output can be stored.
+
+pub fn fnv1a_64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn fnv1a_128(data: &[u8]) -> u128 {
+    data.iter()
+        .fold(0x6c62272e07bb014262b821756295c58d, |hash, byte| {
+            (hash ^ *byte as u128).wrapping_mul(0x1000000000000000000013b)
+        })
+}
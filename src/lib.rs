@@ -1,5 +1,16 @@
 pub mod mtgo;
 pub mod tcdecks;
 
-pub mod deck;
+pub mod archive;
+pub mod cards;
+pub mod config;
 pub mod db;
+pub mod deck;
+pub mod error;
+pub mod fetcher;
+pub mod hash;
+pub mod migrations;
+pub mod ratelimit;
+pub mod robots;
+pub mod source;
+pub mod validate;
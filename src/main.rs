@@ -1,12 +1,751 @@
+use chrono::prelude::{NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use rusqlite::{Connection, Result};
-use deck_list_scraper::{mtgo, tcdecks, db};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
 
-fn main() -> Result<()> {
-    let conn = Connection::open("decklists.db")?;
+use deck_list_scraper::archive::Archive;
+use deck_list_scraper::config::{Config, HttpMode, SourceConfig};
+use deck_list_scraper::deck::{DeckPrice, DecklistFilter, Format, PriceTotal};
+use deck_list_scraper::fetcher::{
+    Fetcher, HttpTransport, RecordTransport, ReplayTransport, Transport,
+};
+use deck_list_scraper::mtgo::Mtgo;
+use deck_list_scraper::ratelimit::RateLimiter;
+use deck_list_scraper::source::{ScrapeOptions, Source};
+use deck_list_scraper::tcdecks::Tcdecks;
+use deck_list_scraper::{cards, db, migrations, source, validate};
+
+#[derive(Parser)]
+#[command(about = "Scrape Magic: The Gathering decklists into a SQLite database")]
+struct Cli {
+    /// Path to the config file, defaults to `config.toml` if it exists
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Path to the SQLite database, overrides the config file
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+
+    /// Don't keep copies of fetched pages, overrides the config file
+    #[arg(long, global = true)]
+    no_archive: bool,
+
+    /// `live`, `record` or `replay`, overrides the config file
+    #[arg(long, global = true, value_parser = parse_http_mode)]
+    http_mode: Option<HttpMode>,
+
+    /// Directory for recorded responses, overrides the config file
+    #[arg(long, global = true)]
+    fixtures: Option<PathBuf>,
+
+    /// Delay between requests in milliseconds, overrides the config file
+    #[arg(long, global = true)]
+    delay: Option<u64>,
+
+    /// Request timeout in seconds, overrides the config file
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Events scraped at once per source, overrides the config file
+    #[arg(long, global = true)]
+    concurrency: Option<usize>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Scrape new decklists from the sources
+    Scrape {
+        #[command(flatten)]
+        sources: SourceArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Keep paging past already scraped events, back to `--since` or
+        /// the configured `start_date`
+        #[arg(long)]
+        backfill: bool,
+    },
+    /// Retry links that failed to scrape previously
+    RescrapeFailed {
+        #[command(flatten)]
+        sources: SourceArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Give up on a link after this many attempts, overrides the config file
+        #[arg(long)]
+        max_attempts: Option<u32>,
+        /// Also retry links that failed to parse
+        #[arg(long)]
+        parse_errors: bool,
+    },
+    /// Rerun the parsers over archived pages without network access
+    Reparse {
+        #[command(flatten)]
+        sources: SourceArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Fetch card data from Scryfall bulk data
+    FetchCards {
+        /// Import a downloaded `oracle-cards` or `default-cards` file instead
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Export decklists as JSON
+    Export {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print database statistics
+    Stats,
+    /// List cards without Scryfall data and suggest cards they could be
+    UnresolvedCards {
+        /// Suggestions to show per card
+        #[arg(long, default_value_t = 3)]
+        suggestions: usize,
+    },
+    /// Accept a suggestion: make NAME an alias of INTO and move its decks over
+    ResolveCard {
+        /// Name of the card without Scryfall data
+        name: String,
+        /// Name of the card with Scryfall data
+        into: String,
+    },
+    /// Check decks against the rules of their format and store the violations
+    Validate {
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Print the average paper and MTGO price of decks per format
+    Prices {
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Price decks as of this day (YYYY-MM-DD), defaults to today
+        #[arg(long)]
+        date: Option<NaiveDate>,
+    },
+    /// Merge cards that are different spellings of the same card
+    MergeCards {
+        /// Only list the cards that would be merged
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove decks that were scraped more than once
+    Dedupe {
+        /// Only count the duplicates
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manage the database
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Only list the pending migrations
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args)]
+struct SourceArgs {
+    /// Only scrape these sources, defaults to the ones enabled in the config
+    #[arg(long, value_enum, value_delimiter = ',')]
+    source: Vec<SourceName>,
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Only include these formats, e.g. `legacy,premodern`
+    #[arg(long, value_parser = parse_format, value_delimiter = ',')]
+    format: Vec<Format>,
+    /// Only include events on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    since: Option<NaiveDate>,
+    /// Only include events on or before this date (YYYY-MM-DD)
+    #[arg(long)]
+    until: Option<NaiveDate>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum SourceName {
+    Tcdecks,
+    Mtgo,
+}
+
+impl From<FilterArgs> for DecklistFilter {
+    fn from(args: FilterArgs) -> Self {
+        DecklistFilter {
+            formats: args.format,
+            since: args.since,
+            until: args.until,
+        }
+    }
+}
+
+impl SourceArgs {
+    fn sources(&self, config: &Config) -> Result<Vec<Box<dyn Source>>, Box<dyn std::error::Error>> {
+        let selected = |name, enabled| {
+            if self.source.is_empty() {
+                enabled
+            } else {
+                self.source.contains(&name)
+            }
+        };
+        let mut sources: Vec<Box<dyn Source>> = Vec::new();
+        let limiter = Arc::new(RateLimiter::new());
+
+        if selected(SourceName::Tcdecks, config.tcdecks.enabled) {
+            let fetcher = live_fetcher(config, &limiter, "tcdecks", &config.tcdecks)?;
+            sources.push(Box::new(Tcdecks::new(config.tcdecks.clone(), fetcher)));
+        }
+        if selected(SourceName::Mtgo, config.mtgo.enabled) {
+            let fetcher = live_fetcher(config, &limiter, "mtgo", &config.mtgo)?;
+            sources.push(Box::new(Mtgo::new(config.mtgo.clone(), fetcher)));
+        }
+
+        Ok(sources)
+    }
+
+    fn offline_sources(
+        &self,
+        config: &Config,
+    ) -> Result<Vec<Box<dyn Source>>, Box<dyn std::error::Error>> {
+        let selected = |name| self.source.is_empty() || self.source.contains(&name);
+        let mut sources: Vec<Box<dyn Source>> = Vec::new();
+
+        if selected(SourceName::Tcdecks) {
+            let fetcher = Fetcher::offline("tcdecks", Archive::open(&config.database)?);
+            sources.push(Box::new(Tcdecks::new(config.tcdecks.clone(), fetcher)));
+        }
+        if selected(SourceName::Mtgo) {
+            let fetcher = Fetcher::offline("mtgo", Archive::open(&config.database)?);
+            sources.push(Box::new(Mtgo::new(config.mtgo.clone(), fetcher)));
+        }
+
+        Ok(sources)
+    }
+}
+
+/// Builds the fetcher for a source according to the configured http mode.
+fn live_fetcher(
+    config: &Config,
+    limiter: &Arc<RateLimiter>,
+    name: &str,
+    source_config: &SourceConfig,
+) -> Result<Fetcher, Box<dyn std::error::Error>> {
+    let http = || {
+        HttpTransport::new(source_config, limiter.clone())
+            .map_err(|e| format!("invalid http settings for {}: {}", name, e))
+    };
+    let transport: Box<dyn Transport> = match config.http.mode {
+        HttpMode::Live => Box::new(http()?),
+        HttpMode::Record => Box::new(RecordTransport::new(
+            Box::new(http()?),
+            &config.http.fixtures,
+        )),
+        HttpMode::Replay => Box::new(ReplayTransport::new(&config.http.fixtures)),
+    };
+    let fetcher = Fetcher::new(name, transport);
+
+    // Replayed pages are already on disk
+    if config.archive && config.http.mode != HttpMode::Replay {
+        Ok(fetcher.with_archive(Archive::open(&config.database)?))
+    } else {
+        Ok(fetcher)
+    }
+}
+
+fn parse_http_mode(value: &str) -> Result<HttpMode, String> {
+    match value {
+        "live" => Ok(HttpMode::Live),
+        "record" => Ok(HttpMode::Record),
+        "replay" => Ok(HttpMode::Replay),
+        _ => Err(format!("unknown http mode: {value}")),
+    }
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match Format::from(value.to_lowercase().as_str()) {
+        Format::Unknown => Err(format!("unknown format: {value}")),
+        format => Ok(format),
+    }
+}
+
+fn run_scrape(
+    conn: &Connection,
+    config: &Config,
+    sources: SourceArgs,
+    options: ScrapeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    source::scrape(conn, &sources.sources(config)?, &options)?;
+
+    Ok(())
+}
+
+fn run_rescrape_failed(
+    conn: &Connection,
+    config: &Config,
+    sources: SourceArgs,
+    options: ScrapeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    source::rescrape_failed(conn, &sources.sources(config)?, &options)?;
+
+    Ok(())
+}
+
+fn run_reparse(
+    conn: &Connection,
+    config: &Config,
+    sources: SourceArgs,
+    options: ScrapeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for source in sources.offline_sources(config)? {
+        source::reparse(conn, source.as_ref(), &options)?;
+    }
+
+    Ok(())
+}
+
+fn export(
+    conn: &Connection,
+    filter: &DecklistFilter,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let decklists = db::find_decklists(conn, filter)?;
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(
+            File::create(&path)
+                .map_err(|e| format!("failed to create {}: {}", path.display(), e))?,
+        ),
+        None => Box::new(io::stdout()),
+    };
+
+    serde_json::to_writer_pretty(&mut writer, &decklists)
+        .map_err(|e| format!("failed to export decklists: {}", e))?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn stats(conn: &Connection) -> Result<()> {
+    println!("Decks:");
+    for (format, count) in db::count_decks_by_format(conn)? {
+        println!("  {format}: {count}");
+    }
+
+    println!("Events: {}", db::count_events(conn)?);
+
+    let (cards, unresolved) = db::count_cards(conn)?;
+    println!("Cards: {cards} ({unresolved} without Scryfall data)");
+
+    let (succeeded, failed) = db::count_scraped_links(conn)?;
+    println!("Scraped links: {succeeded} succeeded, {failed} failed");
+
+    Ok(())
+}
+
+fn unresolved_cards(conn: &Connection, suggestions: usize) -> Result<()> {
+    let unresolved = db::find_unresolved_cards(conn)?;
+    let resolved = db::find_resolved_cards(conn)?;
+
+    for (_, name, decks) in unresolved.iter() {
+        println!("{} ({} decks)", name, decks);
+
+        for (score, _, suggestion) in cards::suggest(name, &resolved, suggestions) {
+            println!("  {:.2} {}", score, suggestion);
+        }
+    }
+
+    println!(
+        "Found {} unresolved cards. Accept a suggestion with `resolve-card <NAME> <INTO>`.",
+        unresolved.len()
+    );
+
+    Ok(())
+}
+
+fn resolve_card(
+    conn: &Connection,
+    name: &str,
+    into: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // NAME as `unresolved-cards` lists it, normalizing it could find INTO
+    let (from, from_name) = match db::find_unresolved_card(conn, name)? {
+        Some(card) => card,
+        // Merging the other way round would delete the card's Scryfall data
+        None => match db::find_card(conn, name)? {
+            Some((_, from_name, true)) => {
+                return Err(format!("{:?} already has Scryfall data", from_name).into())
+            }
+            _ => return Err(format!("no card without Scryfall data named {:?}", name).into()),
+        },
+    };
+    let (into_id, into_name, into_resolved) =
+        db::find_card(conn, into)?.ok_or(format!("no card named {:?}", into))?;
+
+    if !into_resolved {
+        return Err(format!(
+            "{:?} has no Scryfall data, run `fetch-cards` or pick a suggestion",
+            into_name
+        )
+        .into());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    db::merge_card(&tx, from, into_id)?;
+    tx.commit()?;
+
+    println!("{} is now an alias of {}.", from_name, into_name);
+
+    Ok(())
+}
+
+fn validate(conn: &Connection, filter: &DecklistFilter) -> Result<()> {
+    let mut card_data = HashMap::new();
+    let mut illegal = BTreeMap::new();
+    let mut kinds = BTreeMap::new();
+
+    let tx = conn.unchecked_transaction()?;
+
+    for (deck_id, decklist) in db::find_decks(&tx, filter)? {
+        let cards = match card_data.entry(decklist.format) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let cards = db::find_card_data(&tx, &decklist.format)?;
+                if cards.legalities.is_empty() {
+                    eprintln!(
+                        "No legalities for {}, run `fetch-cards` to check banned cards",
+                        decklist.format
+                    );
+                }
+                entry.insert(cards)
+            }
+        };
+
+        let violations = validate::validate(&decklist, cards);
+        db::replace_deck_violations(&tx, deck_id, &violations)?;
+
+        let (total, count) = illegal.entry(decklist.format.to_string()).or_insert((0, 0));
+        *total += 1;
+        if !violations.is_empty() {
+            *count += 1;
+        }
+
+        for violation in violations.iter() {
+            *kinds.entry(violation.kind()).or_insert(0) += 1;
+        }
+    }
+
+    tx.commit()?;
+
+    println!("Illegal decks:");
+    for (format, (total, count)) in illegal {
+        println!("  {format}: {count} of {total}");
+    }
+
+    println!("Violations:");
+    for (kind, count) in kinds {
+        println!("  {kind}: {count}");
+    }
+
+    Ok(())
+}
+
+fn prices(conn: &Connection, filter: &DecklistFilter, date: NaiveDate) -> Result<()> {
+    let mut formats = BTreeMap::new();
+
+    for (deck_id, decklist) in db::find_decks(conn, filter)? {
+        let price = db::find_deck_price(conn, deck_id, date)?;
+        let (count, total) = formats
+            .entry(decklist.format.to_string())
+            .or_insert((0, DeckPrice::default()));
+
+        *count += 1;
+        for (total, price) in [
+            (&mut total.usd, price.usd),
+            (&mut total.eur, price.eur),
+            (&mut total.tix, price.tix),
+        ] {
+            total.total += price.total;
+            total.missing += price.missing;
+        }
+    }
+
+    println!("Average deck prices on {}:", date);
+    for (format, (count, total)) in formats {
+        let average = |total: PriceTotal| total.total / count as f64;
+
+        println!(
+            "  {format}: ${:.2}, €{:.2}, {:.2} tix over {count} decks",
+            average(total.usd),
+            average(total.eur),
+            average(total.tix)
+        );
+
+        if total.usd.missing + total.eur.missing + total.tix.missing > 0 {
+            println!(
+                "    without prices: {} cards in USD, {} in EUR, {} in tix",
+                total.usd.missing, total.eur.missing, total.tix.missing
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_cards(conn: &Connection, dry_run: bool) -> Result<()> {
+    let merges = db::merge_duplicate_cards(conn, dry_run)?;
+
+    for (from, into) in merges.iter() {
+        println!("{} -> {}", from, into);
+    }
+
+    if dry_run {
+        println!("Found {} cards to merge.", merges.len());
+    } else {
+        println!("Merged {} cards.", merges.len());
+    }
+
+    Ok(())
+}
+
+fn dedupe(conn: &Connection, dry_run: bool) -> Result<()> {
+    let (deleted, updated) = db::dedupe_decks(conn, dry_run)?;
+
+    if dry_run {
+        println!(
+            "Found {} duplicate decks and {} outdated fingerprints.",
+            deleted, updated
+        );
+    } else {
+        println!(
+            "Deleted {} duplicate decks, updated {} fingerprints.",
+            deleted, updated
+        );
+    }
+
+    Ok(())
+}
+
+fn migrate(conn: &Connection, dry_run: bool) -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "Database is at schema version {}, latest is {}.",
+        migrations::current_version(conn)?,
+        migrations::latest_version()
+    );
+
+    if dry_run {
+        let pending = migrations::pending(conn)?;
+        println!("{} pending migrations.", pending.len());
+        for (version, migration) in pending {
+            println!("{}: {}", version, migration.description);
+        }
+    } else {
+        let applied = migrations::migrate(conn)?;
+        println!("Applied {} migrations.", applied);
+    }
+
+    Ok(())
+}
+
+fn load_config(cli: &Cli) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut config = Config::load(cli.config.as_deref())?;
+
+    if let Some(db) = &cli.db {
+        config.database = db.clone();
+    }
+
+    if cli.no_archive {
+        config.archive = false;
+    }
+
+    if let Some(mode) = cli.http_mode {
+        config.http.mode = mode;
+    }
+
+    if let Some(fixtures) = &cli.fixtures {
+        config.http.fixtures = fixtures.clone();
+    }
+
+    if let Some(concurrency) = cli.concurrency {
+        config.concurrency = concurrency;
+    }
+
+    for source in [&mut config.mtgo, &mut config.tcdecks] {
+        if let Some(delay) = cli.delay {
+            source.delay = delay;
+        }
+        if let Some(timeout) = cli.timeout {
+            source.timeout = timeout;
+        }
+    }
+
+    Ok(config)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    let config = load_config(&cli)?;
+    let conn = Connection::open(&config.database)?;
+
+    if let Command::Db {
+        command: DbCommand::Migrate { dry_run },
+    } = cli.command
+    {
+        return migrate(&conn, dry_run);
+    }
 
     db::setup(&conn)?;
-    tcdecks::scrape(&conn)?;
-    mtgo::scrape(&conn)?;
+
+    match cli.command {
+        Command::Scrape {
+            sources,
+            filter,
+            backfill,
+        } => {
+            let options = ScrapeOptions {
+                filter: filter.into(),
+                retry: config.retry.clone(),
+                backfill,
+                concurrency: config.concurrency,
+            };
+            run_scrape(&conn, &config, sources, options)?
+        }
+        Command::RescrapeFailed {
+            sources,
+            filter,
+            max_attempts,
+            parse_errors,
+        } => {
+            let mut retry = config.retry.clone();
+            if let Some(max_attempts) = max_attempts {
+                retry.max_attempts = max_attempts;
+            }
+            retry.retry_parse_errors |= parse_errors;
+
+            let options = ScrapeOptions {
+                filter: filter.into(),
+                retry,
+                backfill: false,
+                concurrency: config.concurrency,
+            };
+            run_rescrape_failed(&conn, &config, sources, options)?
+        }
+        Command::Reparse { sources, filter } => {
+            let options = ScrapeOptions {
+                filter: filter.into(),
+                ..Default::default()
+            };
+            run_reparse(&conn, &config, sources, options)?
+        }
+        Command::FetchCards { file: None } => cards::fetch_scryfall_cards(&conn)?,
+        Command::FetchCards { file: Some(file) } => {
+            cards::import_scryfall_cards(&conn, &file)?;
+        }
+        Command::Export { filter, output } => export(&conn, &filter.into(), output)?,
+        Command::Stats => stats(&conn)?,
+        Command::UnresolvedCards { suggestions } => unresolved_cards(&conn, suggestions)?,
+        Command::ResolveCard { name, into } => resolve_card(&conn, &name, &into)?,
+        Command::Validate { filter } => validate(&conn, &filter.into())?,
+        Command::Prices { filter, date } => prices(
+            &conn,
+            &filter.into(),
+            date.unwrap_or_else(|| Utc::now().naive_utc().date()),
+        )?,
+        Command::MergeCards { dry_run } => merge_cards(&conn, dry_run)?,
+        Command::Dedupe { dry_run } => dedupe(&conn, dry_run)?,
+        Command::Db { .. } => unreachable!("handled before setup"),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const DEFAULT_CARDS: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/scryfall/default-cards.json"
+    );
+
+    /// Cards from the Scryfall fixture, and a deck with two legacy spellings
+    /// of Lightning Bolt stored before names were normalized.
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::setup(&conn).unwrap();
+        cards::import_scryfall_cards(&conn, Path::new(DEFAULT_CARDS)).unwrap();
+
+        conn.execute_batch(
+            "INSERT INTO cards (id, name) VALUES (100, 'Lightning  Bolt'), (101, 'Lightnig Bolt');
+            INSERT INTO decks (id, format) VALUES (1, 'legacy');
+            INSERT INTO deck_cards (deck_id, card_id, count, is_sideboard)
+                VALUES (1, 100, 2, 0), (1, 101, 2, 1);",
+        )
+        .unwrap();
+
+        conn
+    }
+
+    #[test]
+    fn accepting_suggestions_merges_the_unresolved_cards() {
+        let conn = setup();
+
+        let resolved = db::find_resolved_cards(&conn).unwrap();
+        for (_, name, decks) in db::find_unresolved_cards(&conn).unwrap() {
+            assert_eq!(decks, 1);
+
+            let (score, _, suggestion) = cards::suggest(&name, &resolved, 1)[0];
+            assert!(score > 0.9);
+            assert_eq!(suggestion, "Lightning Bolt");
+
+            resolve_card(&conn, &name, suggestion).unwrap();
+        }
+
+        assert_eq!(db::count_cards(&conn).unwrap(), (3, 0));
+
+        let bolt = db::find_card(&conn, "Lightning Bolt").unwrap().unwrap().0;
+        assert_eq!(
+            db::find_card(&conn, "lightnig bolt").unwrap().unwrap().0,
+            bolt
+        );
+
+        let counts: Vec<(i64, usize)> = conn
+            .prepare("SELECT card_id, count FROM deck_cards ORDER BY is_sideboard")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(counts, vec![(bolt, 2), (bolt, 2)]);
+    }
+
+    #[test]
+    fn cards_with_scryfall_data_are_not_merged_away() {
+        let conn = setup();
+
+        assert!(resolve_card(&conn, "Lightning Bolt", "Fire // Ice").is_err());
+        assert!(resolve_card(&conn, "Lightning  Bolt", "Lightnig Bolt").is_err());
+        assert!(resolve_card(&conn, "Lightning  Bolt", "Counterspell").is_err());
+        assert_eq!(db::count_cards(&conn).unwrap(), (5, 2));
+    }
+}
@@ -0,0 +1,758 @@
+use rusqlite::{params, Connection, Result};
+use std::collections::{HashMap, HashSet};
+
+use crate::cards;
+use crate::deck::{Decklist, Format};
+use crate::mtgo;
+
+/// A step in the schema history. The position of a migration in
+/// [`MIGRATIONS`] is its version, and the version of a database is kept in
+/// `PRAGMA user_version`. Migrations are only ever appended.
+pub struct Migration {
+    pub description: &'static str,
+    apply: fn(&Connection) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "initial schema",
+        apply: initial_schema,
+    },
+    Migration {
+        description: "unique deck fingerprints",
+        apply: deck_fingerprints,
+    },
+    Migration {
+        description: "events table",
+        apply: events,
+    },
+    Migration {
+        description: "deck provenance",
+        apply: deck_provenance,
+    },
+    Migration {
+        description: "card aliases",
+        apply: card_aliases,
+    },
+    Migration {
+        description: "card legalities",
+        apply: card_legalities,
+    },
+    Migration {
+        description: "deck violations",
+        apply: deck_violations,
+    },
+    Migration {
+        description: "oracle data and card faces",
+        apply: oracle_data,
+    },
+    Migration {
+        description: "card prices",
+        apply: card_prices,
+    },
+    Migration {
+        description: "cards keyed by oracle_id",
+        apply: card_oracle_ids,
+    },
+    Migration {
+        description: "scraped link sources",
+        apply: scraped_link_sources,
+    },
+    Migration {
+        description: "mtgo link formats",
+        apply: mtgo_link_formats,
+    },
+];
+
+/// The version the current code expects.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Migrations that haven't been applied to the database yet, with their
+/// versions.
+pub fn pending(conn: &Connection) -> Result<Vec<(u32, &'static Migration)>> {
+    let current = current_version(conn)?;
+
+    Ok(MIGRATIONS
+        .iter()
+        .enumerate()
+        .map(|(index, migration)| (index as u32 + 1, migration))
+        .filter(|(version, _)| *version > current)
+        .collect())
+}
+
+/// Applies every pending migration, each in its own transaction together with
+/// the version bump. Returns the number of migrations applied.
+pub fn migrate(conn: &Connection) -> Result<usize> {
+    let current = current_version(conn)?;
+
+    if current > latest_version() {
+        eprintln!(
+            "Database is at schema version {}, newer than the {} this build knows about",
+            current,
+            latest_version()
+        );
+    }
+
+    let pending = pending(conn)?;
+
+    for (version, migration) in pending.iter() {
+        let tx = conn.unchecked_transaction()?;
+
+        (migration.apply)(&tx)?;
+        tx.execute_batch(&format!("PRAGMA user_version = {version}"))?;
+
+        tx.commit()?;
+
+        println!("Applied migration {}: {}", version, migration.description);
+    }
+
+    Ok(pending.len())
+}
+
+/// The schema as it was before migrations were tracked. Databases from that
+/// time may be missing any of the later columns, so they are added here when
+/// needed. Later migrations can assume this schema exactly.
+fn initial_schema(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS decks (
+                id INTEGER PRIMARY KEY,
+                name TEXT,
+                format TEXT NOT NULL,
+                event TEXT,
+                date TEXT,
+                player TEXT,
+                archetype TEXT,
+                result TEXT,
+                event_link TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS cards (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                scryfall_id TEXT,
+                scryfall_url TEXT,
+                cmc REAL,
+                power TEXT,
+                toughness TEXT,
+                type_line TEXT,
+                set_code TEXT,
+                set_name TEXT,
+                colors TEXT,
+                is_premodern_legal INTEGER
+            )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS deck_cards (
+                deck_id INTEGER,
+                card_id INTEGER,
+                count INTEGER,
+                is_sideboard BOOLEAN,
+                FOREIGN KEY(deck_id) REFERENCES decks(id),
+                FOREIGN KEY(card_id) REFERENCES cards(id)
+            )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS scraped_links (
+                id INTEGER PRIMARY KEY,
+                link TEXT NOT NULL UNIQUE,
+                source TEXT,
+                format TEXT,
+                is_success BOOLEAN,
+                error_kind TEXT,
+                error_msg TEXT,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_attempt_at DATETIME,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS raw_pages (
+                id INTEGER PRIMARY KEY,
+                source TEXT NOT NULL,
+                url TEXT NOT NULL,
+                fetched_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                body BLOB NOT NULL
+            )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS raw_pages_url ON raw_pages (url, fetched_at)",
+        [],
+    )?;
+
+    add_column_if_missing(conn, "decks", "event_link", "TEXT")?;
+    add_column_if_missing(conn, "scraped_links", "source", "TEXT")?;
+    add_column_if_missing(conn, "scraped_links", "format", "TEXT")?;
+    add_column_if_missing(conn, "scraped_links", "error_kind", "TEXT")?;
+    add_column_if_missing(
+        conn,
+        "scraped_links",
+        "attempts",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(conn, "scraped_links", "last_attempt_at", "DATETIME")?;
+
+    Ok(())
+}
+
+/// Fingerprints the oldest copy of every deck. Later copies are left without
+/// one for `dedupe` to remove, the unique index doesn't cover NULLs.
+fn deck_fingerprints(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE decks ADD COLUMN fingerprint TEXT", [])?;
+
+    let mut decks = conn
+        .prepare("SELECT id, format, event, date, player FROM decks ORDER BY id")?
+        .query_map([], |row| {
+            let format: String = row.get(1)?;
+            let date: Option<String> = row.get(3)?;
+
+            Ok((
+                row.get::<_, i64>(0)?,
+                Decklist {
+                    format: format.as_str().into(),
+                    event: row.get(2)?,
+                    date: date.and_then(|d| d.parse().ok()),
+                    player: row.get(4)?,
+                    archetype: None,
+                    result: None,
+                    name: None,
+                    source: None,
+                    source_url: None,
+                    external_id: None,
+                    mainboard: Vec::new(),
+                    sideboard: Vec::new(),
+                },
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut cards = conn.prepare(
+        "SELECT deck_cards.deck_id, deck_cards.count, cards.name, deck_cards.is_sideboard
+            FROM deck_cards
+            JOIN cards ON cards.id = deck_cards.card_id",
+    )?;
+    let indices = decks
+        .iter()
+        .enumerate()
+        .map(|(index, (deck_id, _))| (*deck_id, index))
+        .collect::<HashMap<_, _>>();
+
+    for card in cards.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, usize>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, bool>(3)?,
+        ))
+    })? {
+        let (deck_id, count, name, is_sideboard) = card?;
+
+        if let Some(index) = indices.get(&deck_id) {
+            let decklist = &mut decks[*index].1;
+            if is_sideboard {
+                decklist.sideboard.push((count, name));
+            } else {
+                decklist.mainboard.push((count, name));
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut duplicates = 0;
+
+    for (deck_id, decklist) in decks.iter() {
+        let fingerprint = decklist.fingerprint();
+
+        if seen.insert(fingerprint.clone()) {
+            conn.execute(
+                "UPDATE decks SET fingerprint = ?1 WHERE id = ?2",
+                params![fingerprint, deck_id],
+            )?;
+        } else {
+            duplicates += 1;
+        }
+    }
+
+    conn.execute(
+        "CREATE UNIQUE INDEX decks_fingerprint ON decks (fingerprint)",
+        [],
+    )?;
+
+    if duplicates > 0 {
+        println!(
+            "Found {} duplicate decks, run `dedupe` to remove them",
+            duplicates
+        );
+    }
+
+    Ok(())
+}
+
+/// Creates an event for every scraped link that has decks. Decks from before
+/// links were recorded get an event without a url for each format, event
+/// name and date.
+fn events(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE events (
+                id INTEGER PRIMARY KEY,
+                source TEXT,
+                url TEXT UNIQUE,
+                name TEXT,
+                format TEXT NOT NULL,
+                date TEXT,
+                event_type TEXT,
+                players INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+        ALTER TABLE decks ADD COLUMN event_id INTEGER REFERENCES events(id);
+        CREATE INDEX decks_event_id ON decks (event_id);
+
+        INSERT INTO events (source, url, name, format, date)
+            SELECT MIN(scraped_links.source), decks.event_link, MIN(TRIM(decks.event)),
+                MIN(decks.format), MIN(decks.date)
+            FROM decks
+            LEFT JOIN scraped_links ON scraped_links.link = decks.event_link
+            WHERE decks.event_link IS NOT NULL
+            GROUP BY decks.event_link;
+
+        UPDATE decks
+            SET event_id = (SELECT id FROM events WHERE events.url = decks.event_link)
+            WHERE event_link IS NOT NULL;
+
+        INSERT INTO events (name, format, date)
+            SELECT DISTINCT TRIM(event), format, date FROM decks WHERE event_id IS NULL;
+
+        UPDATE decks
+            SET event_id = (
+                SELECT id FROM events
+                WHERE events.url IS NULL
+                  AND events.format = decks.format
+                  AND events.name IS TRIM(decks.event)
+                  AND events.date IS decks.date
+            )
+            WHERE event_id IS NULL;",
+    )
+}
+
+/// Older decks only get their source, from the event they belong to.
+fn deck_provenance(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE decks ADD COLUMN source TEXT;
+        ALTER TABLE decks ADD COLUMN source_url TEXT;
+        ALTER TABLE decks ADD COLUMN external_id TEXT;
+        CREATE INDEX decks_external_id ON decks (source, external_id);
+
+        UPDATE decks
+            SET source = (SELECT source FROM events WHERE events.id = decks.event_id);",
+    )
+}
+
+/// `alias` is the [`crate::cards::name_key`] of a spelling.
+fn card_aliases(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE card_aliases (
+                alias TEXT PRIMARY KEY,
+                card_id INTEGER NOT NULL,
+                FOREIGN KEY(card_id) REFERENCES cards(id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Filled by the next `fetch-cards`, `is_premodern_legal` is kept as it was.
+fn card_legalities(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE card_legalities (
+                card_id INTEGER NOT NULL,
+                format TEXT NOT NULL,
+                legality TEXT NOT NULL,
+                PRIMARY KEY (card_id, format),
+                FOREIGN KEY(card_id) REFERENCES cards(id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn deck_violations(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE deck_violations (
+                id INTEGER PRIMARY KEY,
+                deck_id INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                card TEXT,
+                message TEXT NOT NULL,
+                FOREIGN KEY(deck_id) REFERENCES decks(id)
+            );
+
+        CREATE INDEX deck_violations_deck_id ON deck_violations (deck_id);",
+    )
+}
+
+/// Filled by the next `fetch-cards`.
+fn oracle_data(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE cards ADD COLUMN mana_cost TEXT;
+        ALTER TABLE cards ADD COLUMN oracle_text TEXT;
+        ALTER TABLE cards ADD COLUMN color_identity TEXT;
+        ALTER TABLE cards ADD COLUMN keywords TEXT;
+        ALTER TABLE cards ADD COLUMN rarity TEXT;
+        ALTER TABLE cards ADD COLUMN oracle_id TEXT;
+        ALTER TABLE cards ADD COLUMN layout TEXT;
+
+        CREATE TABLE card_faces (
+                card_id INTEGER NOT NULL,
+                face_index INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                mana_cost TEXT,
+                type_line TEXT,
+                oracle_text TEXT,
+                colors TEXT,
+                power TEXT,
+                toughness TEXT,
+                loyalty TEXT,
+                PRIMARY KEY (card_id, face_index),
+                FOREIGN KEY(card_id) REFERENCES cards(id)
+            );",
+    )
+}
+
+/// One row per card and day of import, prices are `NULL` when Scryfall has
+/// none in that currency.
+fn card_prices(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE card_prices (
+                card_id INTEGER NOT NULL,
+                date TEXT NOT NULL,
+                usd REAL,
+                eur REAL,
+                tix REAL,
+                PRIMARY KEY (card_id, date),
+                FOREIGN KEY(card_id) REFERENCES cards(id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Cards renamed by Scryfall got a second row under the new name. The newer
+/// row is the one still being updated, the older ones are merged into it and
+/// their names kept as aliases.
+fn card_oracle_ids(conn: &Connection) -> Result<()> {
+    let renamed = conn
+        .prepare(
+            "SELECT cards.id, cards.name, newest.id
+                FROM cards
+                JOIN (SELECT oracle_id, max(id) AS id FROM cards
+                        WHERE oracle_id IS NOT NULL
+                        GROUP BY oracle_id) AS newest
+                    ON newest.oracle_id = cards.oracle_id
+                WHERE cards.id != newest.id",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (from, name, into) in renamed {
+        conn.execute(
+            "UPDATE deck_cards SET card_id = ?2 WHERE card_id = ?1",
+            [from, into],
+        )?;
+        conn.execute(
+            "UPDATE card_aliases SET card_id = ?2 WHERE card_id = ?1",
+            [from, into],
+        )?;
+        conn.execute(
+            "INSERT INTO card_aliases (alias, card_id) VALUES (?1, ?2)
+                ON CONFLICT(alias) DO UPDATE SET card_id = excluded.card_id",
+            params![cards::name_key(&name), into],
+        )?;
+        conn.execute(
+            "UPDATE OR IGNORE card_prices SET card_id = ?2 WHERE card_id = ?1",
+            [from, into],
+        )?;
+        conn.execute("DELETE FROM card_prices WHERE card_id = ?1", [from])?;
+        conn.execute("DELETE FROM card_faces WHERE card_id = ?1", [from])?;
+        conn.execute("DELETE FROM card_legalities WHERE card_id = ?1", [from])?;
+        conn.execute("DELETE FROM cards WHERE id = ?1", [from])?;
+    }
+
+    conn.execute(
+        "CREATE UNIQUE INDEX cards_oracle_id ON cards (oracle_id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Links scraped before `scraped_links.source` existed have neither a source
+/// nor a format, so `rescrape-failed` never picked them up. The source shows
+/// in the link, the format only in decks saved from it. The events and decks
+/// made from those links are fixed up along with them.
+fn scraped_link_sources(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "UPDATE scraped_links SET source = 'tcdecks'
+            WHERE source IS NULL AND link LIKE 'deck.php%';
+        UPDATE scraped_links SET source = 'mtgo'
+            WHERE source IS NULL
+                AND (link LIKE '/en/%' OR link LIKE 'https://magic.wizards.com/%');
+        UPDATE scraped_links
+            SET format = (SELECT format FROM decks WHERE decks.event_link = scraped_links.link)
+            WHERE format IS NULL;
+
+        UPDATE events
+            SET source = (SELECT source FROM scraped_links WHERE scraped_links.link = events.url)
+            WHERE source IS NULL;
+        UPDATE decks
+            SET source = (SELECT source FROM events WHERE events.id = decks.event_id)
+            WHERE source IS NULL;",
+    )
+}
+
+/// Links that failed before formats were recorded have no decks to take the
+/// format from in [`scraped_link_sources`]. MTGO articles name it in their
+/// link, which is enough for `rescrape-failed` to retry them.
+fn mtgo_link_formats(conn: &Connection) -> Result<()> {
+    let links = conn
+        .prepare("SELECT id, link FROM scraped_links WHERE source = 'mtgo' AND format IS NULL")?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    for (id, link) in links {
+        let format = mtgo::format_from_link(&link);
+        if format != Format::Unknown {
+            conn.execute(
+                "UPDATE scraped_links SET format = ?2 WHERE id = ?1",
+                params![id, format.to_string()],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `CREATE TABLE IF NOT EXISTS` leaves tables of older databases as they
+/// were, so columns added later have to be added separately.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    /// The tables as `db::setup` created them before migrations were
+    /// tracked, with a deck scraped twice, another deck and two links.
+    fn baseline() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE decks (
+                    id INTEGER PRIMARY KEY,
+                    name TEXT,
+                    format TEXT NOT NULL,
+                    event TEXT,
+                    date TEXT,
+                    player TEXT,
+                    archetype TEXT,
+                    result TEXT,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+            CREATE TABLE cards (
+                    id INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL UNIQUE,
+                    scryfall_id TEXT,
+                    scryfall_url TEXT,
+                    cmc REAL,
+                    power TEXT,
+                    toughness TEXT,
+                    type_line TEXT,
+                    set_code TEXT,
+                    set_name TEXT,
+                    colors TEXT,
+                    is_premodern_legal INTEGER
+                );
+            CREATE TABLE deck_cards (
+                    deck_id INTEGER,
+                    card_id INTEGER,
+                    count INTEGER,
+                    is_sideboard BOOLEAN,
+                    FOREIGN KEY(deck_id) REFERENCES decks(id),
+                    FOREIGN KEY(card_id) REFERENCES cards(id)
+                );
+            CREATE TABLE scraped_links (
+                    id INTEGER PRIMARY KEY,
+                    link TEXT NOT NULL UNIQUE,
+                    is_success BOOLEAN,
+                    error_msg TEXT,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+
+            INSERT INTO decks (id, format, event, date, player) VALUES
+                (1, 'premodern', ' Lagarto Open ', '2022-05-21', 'Alice'),
+                (2, 'premodern', 'Lagarto Open', '2022-05-21', 'Alice'),
+                (3, 'premodern', 'Lagarto Open', '2022-05-21', 'Bob'),
+                (4, 'legacy', 'Legacy Challenge', '2024-05-05', 'Carol');
+            INSERT INTO cards (id, name) VALUES (1, 'Lightning Bolt'), (2, 'Mountain');
+            INSERT INTO deck_cards (deck_id, card_id, count, is_sideboard) VALUES
+                (1, 1, 4, 0), (1, 2, 56, 0),
+                (2, 1, 4, 0), (2, 2, 56, 0),
+                (3, 2, 60, 0),
+                (4, 1, 4, 0), (4, 2, 56, 0);
+            INSERT INTO scraped_links (link, is_success, error_msg) VALUES
+                ('deck.php?id=100', 1, NULL),
+                ('/en/news/mtgo-standings/legacy-challenge-2024-05-06', 0, 'timed out');",
+        )
+        .unwrap();
+
+        conn
+    }
+
+    #[test]
+    fn upgrades_a_baseline_database() {
+        let conn = baseline();
+
+        assert_eq!(migrate(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(migrate(&conn).unwrap(), 0);
+
+        // One event per format, trimmed name and date
+        let events = conn
+            .prepare("SELECT name, format, date FROM events ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                (
+                    "Lagarto Open".to_owned(),
+                    "premodern".to_owned(),
+                    "2022-05-21".to_owned()
+                ),
+                (
+                    "Legacy Challenge".to_owned(),
+                    "legacy".to_owned(),
+                    "2024-05-05".to_owned()
+                ),
+            ]
+        );
+        let without_event: usize = conn
+            .query_row(
+                "SELECT COUNT(*) FROM decks WHERE event_id IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(without_event, 0);
+
+        // The copy of Alice's deck is left for `dedupe`, the other
+        // fingerprints are the ones the current code computes
+        let unfingerprinted: Vec<i64> = conn
+            .prepare("SELECT id FROM decks WHERE fingerprint IS NULL")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(unfingerprinted, vec![2]);
+        assert_eq!(db::dedupe_decks(&conn, true).unwrap(), (1, 0));
+
+        let links = conn
+            .prepare(
+                "SELECT source, format, error_kind, attempts, last_attempt_at
+                    FROM scraped_links ORDER BY id",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, u32>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            links,
+            vec![
+                (Some("tcdecks".to_owned()), None, None, 0, None),
+                (
+                    Some("mtgo".to_owned()),
+                    Some("legacy".to_owned()),
+                    None,
+                    0,
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_databases_get_the_latest_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+
+        assert_eq!(migrate(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(pending(&conn).unwrap().is_empty());
+    }
+}
@@ -1,85 +1,164 @@
-use reqwest::blocking::Client;
+use chrono::prelude::NaiveDate;
 use reqwest::Url;
 use select::document::Document;
 use select::node::Node;
 use select::predicate::{Class, Name};
-use std::{fmt, thread, time};
-use std::time::Duration;
-use chrono::prelude::{NaiveDate};
-use rusqlite::{Connection, Result};
+use std::collections::HashSet;
+
+use crate::config::SourceConfig;
+use crate::deck::{Decklist, DecklistFilter, DecklistLinks, Event, Format, ScrapedEvent};
+use crate::error::ScrapeError;
+use crate::fetcher::Fetcher;
+use crate::source::Source;
+
+const DECKLISTS_ENDPOINT: &str = "/en/section-articles-see-more-ajax";
+const QUERY_DATE_FORMAT: &str = "%m/%d/%Y";
+const PAGE_SIZE: usize = 100;
+/// Stop paging here even if the site keeps returning full pages, like the
+/// `page > 100` guard of tcdecks
+const MAX_PAGES: usize = 200;
+/// Kinds of events as they appear in article titles, most specific first
+const EVENT_TYPES: &[&str] = &[
+    "Showcase",
+    "Super Qualifier",
+    "Qualifier",
+    "Preliminary",
+    "Challenge",
+    "League",
+    "Championship",
+];
+
+pub struct Mtgo {
+    fetcher: Fetcher,
+    config: SourceConfig,
+}
 
-use crate::deck::{Format, Decklist, DecklistLinks};
-use crate::db;
+impl Mtgo {
+    pub fn new(config: SourceConfig, fetcher: Fetcher) -> Self {
+        Mtgo { fetcher, config }
+    }
+}
 
-const BASE_URL: &str = "https://magic.wizards.com";
-const DECKLISTS_ENDPOINT: &str = "/en/section-articles-see-more-ajax?dateoff=&l=en&f=9041&search-result-theme=&fromDate=&toDate=&sort=DESC&word=";
-const SLEEP_DELAY: u64 = 1000;
+impl Source for Mtgo {
+    fn name(&self) -> &str {
+        "mtgo"
+    }
 
-#[derive(Debug, Clone)]
-struct NotFoundError;
+    fn find_links(
+        &self,
+        filter: &DecklistFilter,
+        is_scraped: &dyn Fn(&str) -> bool,
+        found: &mut dyn FnMut(Format, String),
+    ) -> Result<(), ScrapeError> {
+        find_latest_decklists(
+            &self.fetcher,
+            &self.config,
+            filter,
+            is_scraped,
+            &mut |format, link| {
+                if self.config.is_format_enabled(&format) {
+                    found(format, link);
+                }
+            },
+        )
+    }
 
-impl fmt::Display for NotFoundError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "article is not found")
+    fn scrape_event(
+        &self,
+        link: &str,
+        format: &Format,
+        _filter: &DecklistFilter,
+    ) -> Result<ScrapedEvent, ScrapeError> {
+        scrape_decklists(&self.fetcher, &self.config.base_url, link, format)
     }
 }
-impl std::error::Error for NotFoundError {}
-
-pub fn scrape(conn: &Connection) -> Result<()> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(60))
-        .build()
-        .unwrap();
-        
-    let links = find_latest_decklists(&client).unwrap();
-
-    println!("Found {} links", links.len());
-
-    for (index, (format, link)) in links.iter().enumerate() {
-        println!("[{}/{}] {}: {}", index + 1, links.len(), format, link);
-
-        match db::find_scraped_link(conn, link)? {
-            Some(scraped) => {
-                if scraped.is_success {
-                    println!("[{}/{}] Already successfully scraped, skipping", index + 1, links.len());
-                    continue;
-                }
-            },
-            None => {},
-        }
 
-        match scrape_decklists(&client, &link, format) {
-            Ok(decklists) => {
-                for decklist in decklists.into_iter() {
-                    if let Err(e) = db::insert_decklist(conn, &decklist) {
-                        eprintln!("Failed to insert decklist: {}", e);
-                    }
-                }
+fn find_latest_decklists(
+    fetcher: &Fetcher,
+    config: &SourceConfig,
+    filter: &DecklistFilter,
+    is_scraped: &dyn Fn(&str) -> bool,
+    found: &mut dyn FnMut(Format, String),
+) -> Result<(), ScrapeError> {
+    let from_date = filter
+        .since
+        .or(config.start_date)
+        .map(|date| date.format(QUERY_DATE_FORMAT).to_string())
+        .unwrap_or_default();
+    let to_date = filter
+        .until
+        .map(|date| date.format(QUERY_DATE_FORMAT).to_string())
+        .unwrap_or_default();
+
+    let mut offset = 0;
+    let mut seen = HashSet::new();
+
+    for _ in 0..MAX_PAGES {
+        let mut url = Url::parse(&config.base_url)?.join(DECKLISTS_ENDPOINT)?;
+        url.query_pairs_mut()
+            .append_pair("dateoff", "")
+            .append_pair("l", "en")
+            .append_pair("f", "9041")
+            .append_pair("search-result-theme", "")
+            .append_pair("fromDate", &from_date)
+            .append_pair("toDate", &to_date)
+            .append_pair("sort", "DESC")
+            .append_pair("word", "")
+            .append_pair("offset", &offset.to_string())
+            .append_pair("limit", &PAGE_SIZE.to_string());
+
+        println!(
+            "[mtgo/{offset}] Scanning article links, total {}",
+            seen.len()
+        );
+
+        let res = fetcher.get(&url)?;
+        let page_links = parse_decklist_links(&res, url.as_str())?;
+        let page_size = page_links.len();
+        let mut new_links = 0;
+
+        for (format, link) in page_links {
+            // Articles are sorted newest first, so everything after an
+            // already scraped article has been seen on an earlier run.
+            if is_scraped(&link) {
+                println!("[mtgo/{offset}] Reached already scraped article {link}");
+                return Ok(());
+            }
 
-                db::insert_scraped_link(conn, &link, true, None)?;
-            },
-            Err(e) => {
-                eprintln!("Failed to scrape decklists: {}", e);
-                db::insert_scraped_link(conn, &link, false, Some(&e.to_string()))?;
+            if seen.insert(link.clone()) {
+                found(format, link);
+                new_links += 1;
             }
         }
 
-        // Lets be polite
-        thread::sleep(time::Duration::from_millis(SLEEP_DELAY));
+        // A site that ignores `offset` serves the same page over and over
+        if page_size < PAGE_SIZE || new_links == 0 {
+            return Ok(());
+        }
+
+        offset += PAGE_SIZE;
     }
 
+    println!("[mtgo/{offset}] Stopped after {MAX_PAGES} pages");
     Ok(())
 }
 
-fn find_latest_decklists(client: &Client) -> Result<Vec<(Format, String)>, Box<dyn std::error::Error>> {
-    let offset = 0;
-    let limit = 100;
-    let url = Url::parse(BASE_URL)?.join(format!("{DECKLISTS_ENDPOINT}&offset={offset}&limit={limit}").as_str())?;
-    let res = client.get(url).send()?.text()?;
+/// The format an article is about, read from its link, e.g. `legacy` for
+/// `/en/news/mtgo-standings/legacy-challenge-32-2024-05-05`. Listings name it
+/// in the title, see [`parse_decklist_links`].
+pub fn format_from_link(link: &str) -> Format {
+    link.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .and_then(|slug| slug.split('-').next())
+        .unwrap_or_default()
+        .into()
+}
 
-    let parsed: DecklistLinks = serde_json::from_str(&res)?;
+fn parse_decklist_links(res: &str, url: &str) -> Result<Vec<(Format, String)>, ScrapeError> {
+    let parsed: DecklistLinks = serde_json::from_str(res)?;
 
-    let links = parsed
+    parsed
         .data
         .into_iter()
         .map(|html_link| {
@@ -87,7 +166,7 @@ fn find_latest_decklists(client: &Client) -> Result<Vec<(Format, String)>, Box<d
             let link_container = document
                 .find(Class("article-item-extended"))
                 .next()
-                .unwrap();
+                .ok_or_else(|| ScrapeError::missing(".article-item-extended", url))?;
 
             let link = link_container
                 .find(Name("a"))
@@ -95,91 +174,132 @@ fn find_latest_decklists(client: &Client) -> Result<Vec<(Format, String)>, Box<d
                 .map(|s| s.to_string())
                 .collect();
 
-            let title_container = document.find(Class("title")).next().unwrap();
+            let title_container = document
+                .find(Class("title"))
+                .next()
+                .ok_or_else(|| ScrapeError::missing(".title", url))?;
 
             let format = title_container
                 .find(Name("h3"))
                 .next()
-                .unwrap()
+                .ok_or_else(|| ScrapeError::missing(".title h3", url))?
                 .text()
                 .to_lowercase()
                 .split(' ')
                 .next()
-                .unwrap()
+                .unwrap_or_default()
                 .into();
 
-            (format, link)
+            Ok((format, link))
         })
-        .collect();
-
-    Ok(links)
+        .collect()
 }
 
 fn scrape_decklists(
-    client: &Client,
+    fetcher: &Fetcher,
+    base_url: &str,
     link: &str,
     format: &Format,
-) -> Result<Vec<Decklist>, Box<dyn std::error::Error>> {
-    let url = Url::parse(BASE_URL)?.join(link)?;
-    let res = client.get(url).send()?.text()?;
+) -> Result<ScrapedEvent, ScrapeError> {
+    let url = Url::parse(base_url)?.join(link)?;
+    let res = fetcher.get(&url)?;
+
+    let decklists = parse_decklists(&res, url.as_str(), format)?;
+    let first = decklists.first();
 
+    let name = Document::from(res.as_str())
+        .find(Name("h1"))
+        .next()
+        .map(|node| node.text().trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .or_else(|| first.and_then(|decklist| decklist.event.clone()));
+
+    let event = Event {
+        source: "mtgo".to_owned(),
+        url: link.to_owned(),
+        event_type: name.as_deref().and_then(parse_event_type),
+        name,
+        format: *format,
+        date: first.and_then(|decklist| decklist.date),
+        // The articles only publish the top decks, not the number of players
+        players: None,
+    };
+
+    Ok(ScrapedEvent { event, decklists })
+}
+
+fn parse_event_type(name: &str) -> Option<String> {
+    EVENT_TYPES
+        .iter()
+        .find(|event_type| name.contains(*event_type))
+        .map(|event_type| event_type.to_string())
+}
+
+fn parse_decklists(res: &str, url: &str, format: &Format) -> Result<Vec<Decklist>, ScrapeError> {
     if res.contains("no result found") {
-        return Err(Box::new(NotFoundError));
+        return Err(ScrapeError::NotFound {
+            url: url.to_owned(),
+        });
     }
 
-    let document = Document::from(res.as_str());
+    let document = Document::from(res);
 
-    let date = document
+    let date = match document
         .find(Class("posted-in"))
         .next()
-        .unwrap()
+        .ok_or_else(|| ScrapeError::missing(".posted-in", url))?
         .children()
         .nth(2)
-        .and_then(|node| {
-            node.text()
-                .trim()
-                .strip_prefix("on ")
-                .and_then(|date_str| NaiveDate::parse_from_str(date_str, "%B %d, %Y").ok())
-        });
+        .and_then(|node| node.text().trim().strip_prefix("on ").map(|s| s.to_owned()))
+    {
+        Some(date_str) => Some(NaiveDate::parse_from_str(&date_str, "%B %d, %Y").map_err(
+            |_| ScrapeError::BadDate {
+                value: date_str,
+                url: url.to_owned(),
+            },
+        )?),
+        None => None,
+    };
 
     let decklist_containers = document.find(Class("deck-group"));
 
-    let decklists = decklist_containers
+    decklist_containers
         .map(|container| {
             let mainboard = container
                 .find(Class("sorted-by-overview-container"))
                 .next()
-                .unwrap()
+                .ok_or_else(|| ScrapeError::missing(".sorted-by-overview-container", url))?
                 .find(Class("row"))
-                .flat_map(|row| parse_card_row(&row))
-                .collect();
+                .filter_map(|row| parse_card_row(&row, url).transpose())
+                .collect::<Result<_, _>>()?;
 
-            let sideboard = container
+            let sideboard = match container
                 .find(Class("sorted-by-sideboard-container"))
                 .next()
-                .map_or_else(|| Vec::new(), |node| {
-                    node.find(Class("row"))
-                        .flat_map(|row| parse_card_row(&row))
-                        .collect()
-                });
-
-            let player = container
+            {
+                Some(node) => node
+                    .find(Class("row"))
+                    .filter_map(|row| parse_card_row(&row, url).transpose())
+                    .collect::<Result<_, _>>()?,
+                None => Vec::new(),
+            };
+
+            let deck_meta = container
                 .find(Class("deck-meta"))
                 .next()
-                .unwrap()
+                .ok_or_else(|| ScrapeError::missing(".deck-meta", url))?;
+
+            let player = deck_meta
                 .find(Name("h4"))
                 .next()
                 .map(|node| node.text().trim().to_owned());
 
-            let event = container
-                .find(Class("deck-meta"))
-                .next()
-                .unwrap()
+            let event = deck_meta
                 .find(Name("h5"))
                 .next()
                 .map(|node| node.text().trim().to_owned());
 
-            Decklist {
+            Ok(Decklist {
                 event,
                 player,
                 format: *format,
@@ -189,18 +309,30 @@ fn scrape_decklists(
                 archetype: None,
                 result: Some("5-0".to_owned()),
                 name: None,
-            }
+                source: Some("mtgo".to_owned()),
+                // The decks have no pages of their own
+                source_url: Some(url.to_owned()),
+                external_id: None,
+            })
         })
-        .collect();
-
-    Ok(decklists)
+        .collect()
 }
 
-fn parse_card_row(card_row: &Node) -> Option<(usize, String)> {
-    let count_str: String = card_row.find(Class("card-count")).next()?.text();
-    let count = count_str.parse::<usize>().ok()?;
-
-    card_row
+/// Rows without a card count aren't cards and are skipped.
+fn parse_card_row(card_row: &Node, url: &str) -> Result<Option<(usize, String)>, ScrapeError> {
+    let count_str: String = match card_row.find(Class("card-count")).next() {
+        Some(node) => node.text(),
+        None => return Ok(None),
+    };
+    let count = count_str
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| ScrapeError::BadCount {
+            value: count_str.clone(),
+            url: url.to_owned(),
+        })?;
+
+    Ok(card_row
         .find(Class("card-name"))
         .next()
         .and_then(|node| {
@@ -208,5 +340,28 @@ fn parse_card_row(card_row: &Node) -> Option<(usize, String)> {
                 .next()
                 .or_else(|| node.children().next())
         })
-        .map(|node| (count, node.text()))
+        .map(|node| (count, node.text())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_link_reads_the_slug() {
+        assert_eq!(
+            format_from_link("/en/news/mtgo-standings/legacy-challenge-32-2024-05-05"),
+            Format::Legacy
+        );
+        assert_eq!(
+            format_from_link(
+                "https://magic.wizards.com/en/news/mtgo-standings/pauper-league-2024-05-06/"
+            ),
+            Format::Pauper
+        );
+        assert_eq!(
+            format_from_link("/en/news/mtgo-standings/showcase-challenge-2024-05-05"),
+            Format::Unknown
+        );
+    }
 }
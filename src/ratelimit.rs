@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Requests per host when nothing else has been configured for it.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Token bucket rate limiter shared by every request, keyed by host.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+struct Bucket {
+    interval: Duration,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(interval: Duration, burst: u32) -> Self {
+        let capacity = burst.max(1) as f64;
+
+        Bucket {
+            interval,
+            capacity,
+            tokens: capacity,
+            refilled_at: Instant::now(),
+            blocked_until: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if !self.interval.is_zero() {
+            let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+            self.tokens = (self.tokens + elapsed / self.interval.as_secs_f64()).min(self.capacity);
+        } else {
+            self.tokens = self.capacity;
+        }
+        self.refilled_at = now;
+    }
+
+    /// Takes a token, or returns how long to wait before trying again.
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        if let Some(blocked_until) = self.blocked_until {
+            if blocked_until > now {
+                return Some(blocked_until - now);
+            }
+            self.blocked_until = None;
+        }
+
+        self.refill(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(self.interval.mul_f64(1.0 - self.tokens))
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows `burst` requests to `host` at once and one more every `interval`.
+    /// A slower rate that is already configured, e.g. from a robots.txt
+    /// `Crawl-delay`, is kept.
+    pub fn configure(&self, host: &str, interval: Duration, burst: u32) {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        match buckets.get_mut(host) {
+            Some(bucket) => {
+                bucket.interval = bucket.interval.max(interval);
+                bucket.capacity = bucket.capacity.min(burst.max(1) as f64);
+            }
+            None => {
+                buckets.insert(host.to_owned(), Bucket::new(interval, burst));
+            }
+        }
+    }
+
+    /// Blocks until a request to `host` is allowed.
+    pub fn acquire(&self, host: &str) {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().unwrap();
+                buckets
+                    .entry(host.to_owned())
+                    .or_insert_with(|| Bucket::new(DEFAULT_INTERVAL, 1))
+                    .take(Instant::now())
+            };
+
+            match wait {
+                Some(wait) => thread::sleep(wait),
+                None => return,
+            }
+        }
+    }
+
+    /// Holds back every request to `host` for `duration`, e.g. after the host
+    /// answered with `Retry-After`.
+    pub fn block(&self, host: &str, duration: Duration) {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(host.to_owned())
+            .or_insert_with(|| Bucket::new(DEFAULT_INTERVAL, 1));
+
+        let until = Instant::now() + duration;
+        bucket.blocked_until = Some(bucket.blocked_until.map_or(until, |b| b.max(until)));
+    }
+}
@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+/// The rules of a robots.txt that apply to our user agent.
+#[derive(Debug, Default, Clone)]
+pub struct Robots {
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// Picks the group with the most specific `User-agent` that is part of
+    /// `user_agent`, falling back to the `*` group.
+    pub fn parse(contents: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_agents = false;
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            if key == "user-agent" {
+                if !in_agents {
+                    groups.push(Group::default());
+                    in_agents = true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_lowercase());
+                }
+                continue;
+            }
+
+            in_agents = false;
+            let group = match groups.last_mut() {
+                Some(group) => group,
+                None => continue,
+            };
+
+            match key.as_str() {
+                "allow" if !value.is_empty() => group.rules.push((true, value.to_owned())),
+                "disallow" if !value.is_empty() => group.rules.push((false, value.to_owned())),
+                // Negative, infinite and NaN delays are ignored
+                "crawl-delay" => {
+                    group.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                }
+                _ => {}
+            }
+        }
+
+        let specificity = |group: &Group| {
+            group
+                .agents
+                .iter()
+                .filter(|agent| *agent != "*" && user_agent.contains(agent.as_str()))
+                .map(|agent| agent.len())
+                .max()
+        };
+
+        let group = groups
+            .iter()
+            .filter_map(|group| specificity(group).map(|s| (s, group)))
+            .max_by_key(|(s, _)| *s)
+            .map(|(_, group)| group)
+            .or_else(|| {
+                groups
+                    .iter()
+                    .find(|group| group.agents.iter().any(|a| a == "*"))
+            });
+
+        match group {
+            Some(group) => Robots {
+                rules: group.rules.clone(),
+                crawl_delay: group.crawl_delay,
+            },
+            None => Robots::default(),
+        }
+    }
+
+    /// `path` includes the query string. The longest matching rule wins,
+    /// `Allow` wins ties.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Prefix match supporting the `*` wildcard and a `$` end anchor.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index == parts.len() - 1;
+
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+User-agent: *
+Disallow: /private
+Allow: /private/public
+Crawl-delay: 2
+
+User-agent: deck-list-scraper
+User-agent: other-bot
+Disallow: /search?*q=
+Disallow: /*.php$
+Crawl-delay: 0.5 # seconds
+";
+
+    #[test]
+    fn picks_the_group_of_our_user_agent() {
+        let robots = Robots::parse(ROBOTS, "Mozilla/5.0 (compatible; deck-list-scraper/0.1)");
+
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(500)));
+        assert!(robots.is_allowed("/private"));
+        assert!(!robots.is_allowed("/search?page=2&q=goblins"));
+        assert!(!robots.is_allowed("/deck.php"));
+        assert!(robots.is_allowed("/deck.php?id=1"));
+    }
+
+    #[test]
+    fn falls_back_to_the_wildcard_group() {
+        let robots = Robots::parse(ROBOTS, "some-crawler");
+
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+        assert!(robots.is_allowed("/"));
+        assert!(!robots.is_allowed("/private/deck"));
+        assert!(robots.is_allowed("/private/public/deck"));
+    }
+
+    #[test]
+    fn allows_everything_without_a_matching_group() {
+        let robots = Robots::parse("User-agent: other-bot\nDisallow: /", "deck-list-scraper");
+
+        assert!(robots.is_allowed("/anything"));
+        assert_eq!(robots.crawl_delay, None);
+    }
+
+    #[test]
+    fn allow_wins_ties() {
+        let robots = Robots::parse("User-agent: *\nDisallow: /a\nAllow: /a", "bot");
+
+        assert!(robots.is_allowed("/a"));
+    }
+
+    #[test]
+    fn ignores_invalid_crawl_delays() {
+        for delay in ["-1", "inf", "NaN", "1e300", "soon"] {
+            let robots = Robots::parse(&format!("User-agent: *\nCrawl-delay: {delay}"), "bot");
+
+            assert_eq!(robots.crawl_delay, None, "Crawl-delay: {delay}");
+        }
+    }
+
+    #[test]
+    fn matches_wildcards_and_anchors() {
+        assert!(matches("/decks", "/decks/1"));
+        assert!(!matches("/decks", "/deck"));
+        assert!(matches("/*.php", "/format.php?format=Legacy"));
+        assert!(matches("/*.php$", "/deck.php"));
+        assert!(!matches("/*.php$", "/deck.php?id=1"));
+        assert!(matches("/a*b*c", "/a-b-c-d"));
+        assert!(!matches("/a*b*c", "/a-c-b"));
+        assert!(matches("/exact$", "/exact"));
+        assert!(!matches("/exact$", "/exactly"));
+    }
+}
@@ -0,0 +1,656 @@
+use chrono::prelude::{NaiveDateTime, Utc};
+use chrono::Duration;
+use rusqlite::{Connection, Result};
+use std::collections::{HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use crate::config::RetryConfig;
+use crate::db;
+use crate::deck::{DecklistFilter, FailureKind, Format, ScrapedEvent, ScrapedLink};
+use crate::error::ScrapeError;
+
+/// A site that publishes decklists. Implementors only need to know how to
+/// find event links and how to turn a single event into decklists, the
+/// bookkeeping around `scraped_links` is handled by [`scrape`]. Events are
+/// scraped from several threads at once, so sources have to be `Sync`.
+pub trait Source: Sync {
+    /// Human readable name used in log output.
+    fn name(&self) -> &str;
+
+    /// Discover links to events that contain decklists and hand each one to
+    /// `found` right away, events are scraped while discovery goes on.
+    /// Sources that list events newest first may stop once `is_scraped`
+    /// returns true for a link.
+    fn find_links(
+        &self,
+        filter: &DecklistFilter,
+        is_scraped: &dyn Fn(&str) -> bool,
+        found: &mut dyn FnMut(Format, String),
+    ) -> Result<(), ScrapeError>;
+
+    /// Fetch an event page and parse the event and every decklist in it.
+    /// Sources that fetch decks one by one may leave them out of events
+    /// whose date `filter` doesn't match.
+    fn scrape_event(
+        &self,
+        link: &str,
+        format: &Format,
+        filter: &DecklistFilter,
+    ) -> Result<ScrapedEvent, ScrapeError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ScrapeOptions {
+    pub filter: DecklistFilter,
+    pub retry: RetryConfig,
+    /// Keep discovering links past ones that were already scraped.
+    pub backfill: bool,
+    /// Events of a single source that are fetched and parsed at once.
+    pub concurrency: usize,
+}
+
+impl Default for ScrapeOptions {
+    fn default() -> Self {
+        ScrapeOptions {
+            filter: DecklistFilter::default(),
+            retry: RetryConfig::default(),
+            backfill: false,
+            concurrency: 1,
+        }
+    }
+}
+
+/// Sent from the scraper threads to the thread that owns the database.
+enum Message {
+    /// A link was handed to the workers
+    Queued,
+    /// Discovery of a source is over, with the links it found and queued
+    Discovered(usize, usize, usize, Result<(), ScrapeError>),
+    Scraped(usize, Format, String, Result<ScrapedEvent, ScrapeError>),
+}
+
+/// Discovers and scrapes new events from every source side by side.
+pub fn scrape(
+    conn: &Connection,
+    sources: &[Box<dyn Source>],
+    options: &ScrapeOptions,
+) -> Result<()> {
+    let mut scraped = HashMap::new();
+
+    if !options.backfill {
+        for source in sources.iter() {
+            let links = db::find_scraped_links(conn, source.name())?
+                .into_iter()
+                .filter(|scraped| scraped.is_success)
+                .map(|scraped| scraped.link)
+                .collect::<HashSet<_>>();
+
+            scraped.insert(source.name().to_owned(), links);
+        }
+    }
+
+    run(conn, sources, options, |source, found| {
+        let is_scraped = |link: &str| {
+            scraped
+                .get(source.name())
+                .is_some_and(|links| links.contains(link))
+        };
+
+        source.find_links(&options.filter, &is_scraped, found)
+    })
+}
+
+/// Retries previously failed links of every source straight from
+/// `scraped_links`, without discovering links again.
+pub fn rescrape_failed(
+    conn: &Connection,
+    sources: &[Box<dyn Source>],
+    options: &ScrapeOptions,
+) -> Result<()> {
+    let mut failed = HashMap::new();
+
+    for source in sources.iter() {
+        let links = db::find_failed_links(conn, source.name())?;
+        let found = links.len();
+        let links = links
+            .into_iter()
+            .filter_map(|scraped| Some((scraped.format?, scraped.link)))
+            .collect::<Vec<_>>();
+
+        if links.len() < found {
+            println!(
+                "Skipped {} failed links of {} without a format, `scrape --backfill` retries them",
+                found - links.len(),
+                source.name()
+            );
+        }
+
+        failed.insert(source.name().to_owned(), links);
+    }
+
+    let unknown = db::count_failed_links_without_source(conn)?;
+    if unknown > 0 {
+        println!(
+            "Skipped {} failed links without a source, `scrape --backfill` retries them",
+            unknown
+        );
+    }
+
+    run(conn, sources, options, |source, found| {
+        for (format, link) in failed.get(source.name()).into_iter().flatten() {
+            found(*format, link.clone());
+        }
+        Ok(())
+    })
+}
+
+/// Runs `discover` for every source on its own thread and hands the links it
+/// finds to `options.concurrency` worker threads per source, which fetch and
+/// parse the events while discovery goes on. Requests still wait for the
+/// shared rate limiter of their host, so this overlaps parsing and the
+/// sources with each other rather than hitting a single site harder. Only
+/// the calling thread touches the database.
+///
+/// This is plain threads rather than async tasks: fetchers use blocking
+/// `reqwest`, the connection can't leave its thread and the rate limits
+/// bound how fast pages come in, not the number of requests in flight.
+fn run<F>(
+    conn: &Connection,
+    sources: &[Box<dyn Source>],
+    options: &ScrapeOptions,
+    discover: F,
+) -> Result<()>
+where
+    F: Fn(&dyn Source, &mut dyn FnMut(Format, String)) -> Result<(), ScrapeError> + Sync,
+{
+    let now = Utc::now().naive_utc();
+
+    // Looked up by the discovery threads, which can't use the connection
+    let known = sources
+        .iter()
+        .map(|source| {
+            Ok(db::find_scraped_links(conn, source.name())?
+                .into_iter()
+                .map(|scraped| (scraped.link.clone(), scraped))
+                .collect::<HashMap<_, _>>())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+
+        for (index, source) in sources.iter().enumerate() {
+            let source = source.as_ref();
+            let (queue, jobs) = mpsc::channel::<(Format, String)>();
+            let jobs = Arc::new(Mutex::new(jobs));
+
+            for _ in 0..options.concurrency.max(1) {
+                let jobs = Arc::clone(&jobs);
+                let tx = tx.clone();
+
+                scope.spawn(move || loop {
+                    let job = jobs.lock().unwrap().recv();
+                    let (format, link) = match job {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+
+                    let result = source.scrape_event(&link, &format, &options.filter);
+                    if tx
+                        .send(Message::Scraped(index, format, link, result))
+                        .is_err()
+                    {
+                        break;
+                    }
+                });
+            }
+
+            let tx = tx.clone();
+            let discover = &discover;
+            let known = &known[index];
+            scope.spawn(move || {
+                // The queue is dropped when discovery ends, also by a panic,
+                // so that the workers exit once it's empty
+                let queue = queue;
+                let mut seen = HashSet::new();
+                let (mut found, mut queued) = (0, 0);
+
+                let result = discover(source, &mut |format, link| {
+                    found += 1;
+
+                    if !options.filter.matches_format(&format)
+                        || !is_due(&options.retry, known.get(&link), now)
+                        || !seen.insert(link.clone())
+                    {
+                        return;
+                    }
+
+                    if tx.send(Message::Queued).is_ok() && queue.send((format, link)).is_ok() {
+                        queued += 1;
+                    }
+                });
+
+                drop(queue);
+                let _ = tx.send(Message::Discovered(index, found, queued, result));
+            });
+        }
+
+        drop(tx);
+
+        let mut queued = 0;
+        let mut done = 0;
+
+        for message in rx {
+            match message {
+                Message::Queued => queued += 1,
+                Message::Discovered(index, found, count, result) => {
+                    let source = sources[index].as_ref();
+
+                    if let Err(e) = result {
+                        eprintln!("Failed to find decklists for {}: {}", source.name(), e);
+                    }
+
+                    println!(
+                        "Found {} links for {}, {} to scrape.",
+                        found,
+                        source.name(),
+                        count
+                    );
+                }
+                Message::Scraped(index, format, link, result) => {
+                    done += 1;
+                    println!("[{}/{}] {}: {}", done, queued, format, link);
+
+                    save_event(
+                        conn,
+                        sources[index].as_ref(),
+                        options,
+                        &format,
+                        &link,
+                        result,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Runs the current parsers over archived pages of every event scraped from
+/// `source` and replaces its decks. `source` should use an offline fetcher.
+pub fn reparse(conn: &Connection, source: &dyn Source, options: &ScrapeOptions) -> Result<()> {
+    let links = db::find_scraped_links(conn, source.name())?
+        .into_iter()
+        .filter(|scraped| {
+            scraped
+                .format
+                .is_some_and(|format| options.filter.matches_format(&format))
+        })
+        .collect::<Vec<_>>();
+
+    println!(
+        "Found {} links to reparse for {}.",
+        links.len(),
+        source.name()
+    );
+
+    let mut replaced = 0;
+
+    for (index, scraped) in links.iter().enumerate() {
+        let format = match scraped.format {
+            Some(format) => format,
+            None => continue,
+        };
+
+        let event = match source.scrape_event(&scraped.link, &format, &options.filter) {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!(
+                    "[{}/{}] Failed to reparse {}: {}",
+                    index + 1,
+                    links.len(),
+                    scraped.link,
+                    e
+                );
+                continue;
+            }
+        };
+
+        if !options.filter.matches_date(event.event.date) {
+            continue;
+        }
+
+        let saved = if scraped.is_success {
+            replace_decklists(conn, &event)
+        } else {
+            db::save_event(conn, &event)
+        };
+
+        let (deleted, inserted) = match saved {
+            Ok(replaced) => replaced,
+            Err(e) => {
+                eprintln!(
+                    "[{}/{}] Failed to save {}, rolled back: {}",
+                    index + 1,
+                    links.len(),
+                    scraped.link,
+                    e
+                );
+                continue;
+            }
+        };
+
+        println!(
+            "[{}/{}] {}: replaced {} decks with {}",
+            index + 1,
+            links.len(),
+            scraped.link,
+            deleted,
+            inserted
+        );
+        replaced += 1;
+    }
+
+    println!(
+        "Reparsed {} of {} links for {}.",
+        replaced,
+        links.len(),
+        source.name()
+    );
+
+    Ok(())
+}
+
+fn save_event(
+    conn: &Connection,
+    source: &dyn Source,
+    options: &ScrapeOptions,
+    format: &Format,
+    link: &str,
+    result: Result<ScrapedEvent, ScrapeError>,
+) -> Result<()> {
+    match result {
+        Ok(event) => {
+            let date = event.event.date;
+
+            if !options.filter.matches_date(date) {
+                println!(
+                    "Event date {} is outside of the requested range, skipping",
+                    date.map_or_else(|| "unknown".to_owned(), |d| d.to_string())
+                );
+                return Ok(());
+            }
+
+            match db::save_event(conn, &event) {
+                Ok((_, inserted)) if inserted < event.decklists.len() => {
+                    println!(
+                        "Skipped {} duplicate decks",
+                        event.decklists.len() - inserted
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // Nothing of the event was kept, retry it like a failed fetch
+                    eprintln!("Failed to save {}, rolled back: {}", link, e);
+                    db::insert_scraped_link(
+                        conn,
+                        source.name(),
+                        format,
+                        link,
+                        Some((FailureKind::Transient, &e.to_string())),
+                    )?;
+                }
+            }
+        }
+        Err(e) => {
+            let kind = e.kind();
+            eprintln!("Failed to scrape {} ({}): {}", link, kind, e);
+            db::insert_scraped_link(
+                conn,
+                source.name(),
+                format,
+                link,
+                Some((kind, &e.to_string())),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Swaps the decks of an already scraped event without counting it as another
+/// attempt in `scraped_links`.
+fn replace_decklists(conn: &Connection, event: &ScrapedEvent) -> Result<(usize, usize)> {
+    let tx = conn.unchecked_transaction()?;
+    let replaced = db::replace_event_decklists(&tx, event)?;
+    tx.commit()?;
+
+    Ok(replaced)
+}
+
+/// Links that were scraped successfully are never due, failed ones once
+/// their backoff has passed.
+fn is_due(retry: &RetryConfig, scraped: Option<&ScrapedLink>, now: NaiveDateTime) -> bool {
+    match scraped {
+        Some(scraped) if scraped.is_success => false,
+        Some(scraped) => is_retry_due(retry, scraped, now),
+        None => true,
+    }
+}
+
+fn is_retry_due(retry: &RetryConfig, scraped: &ScrapedLink, now: NaiveDateTime) -> bool {
+    if scraped.attempts >= retry.max_attempts {
+        return false;
+    }
+
+    if scraped.error_kind == Some(FailureKind::Parse) && !retry.retry_parse_errors {
+        return false;
+    }
+
+    match (
+        scraped.last_attempt_at,
+        Duration::from_std(retry.backoff(scraped.attempts)),
+    ) {
+        (Some(last_attempt_at), Ok(backoff)) => last_attempt_at + backoff <= now,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time;
+
+    /// Finds a second event only once the first one was scraped.
+    struct Pipelined {
+        scraped: mpsc::Sender<String>,
+        scraped_rx: Mutex<mpsc::Receiver<String>>,
+    }
+
+    impl Source for Pipelined {
+        fn name(&self) -> &str {
+            "pipelined"
+        }
+
+        fn find_links(
+            &self,
+            _filter: &DecklistFilter,
+            _is_scraped: &dyn Fn(&str) -> bool,
+            found: &mut dyn FnMut(Format, String),
+        ) -> Result<(), ScrapeError> {
+            found(Format::Legacy, "first".to_owned());
+
+            let scraped = self
+                .scraped_rx
+                .lock()
+                .unwrap()
+                .recv_timeout(time::Duration::from_secs(10));
+            assert_eq!(scraped.as_deref(), Ok("first"));
+
+            found(Format::Legacy, "second".to_owned());
+            Ok(())
+        }
+
+        fn scrape_event(
+            &self,
+            link: &str,
+            _format: &Format,
+            _filter: &DecklistFilter,
+        ) -> Result<ScrapedEvent, ScrapeError> {
+            let _ = self.scraped.send(link.to_owned());
+
+            Err(ScrapeError::NotFound {
+                url: link.to_owned(),
+            })
+        }
+    }
+
+    /// Finds an event and then fails without returning.
+    struct Panicking;
+
+    impl Source for Panicking {
+        fn name(&self) -> &str {
+            "panicking"
+        }
+
+        fn find_links(
+            &self,
+            _filter: &DecklistFilter,
+            _is_scraped: &dyn Fn(&str) -> bool,
+            found: &mut dyn FnMut(Format, String),
+        ) -> Result<(), ScrapeError> {
+            found(Format::Legacy, "first".to_owned());
+            panic!("discovery failed");
+        }
+
+        fn scrape_event(
+            &self,
+            link: &str,
+            _format: &Format,
+            _filter: &DecklistFilter,
+        ) -> Result<ScrapedEvent, ScrapeError> {
+            Err(ScrapeError::NotFound {
+                url: link.to_owned(),
+            })
+        }
+    }
+
+    fn failed_link(attempts: u32, error_kind: FailureKind, hours_ago: i64) -> ScrapedLink {
+        ScrapedLink {
+            id: 1,
+            link: "deck.php?id=1".to_owned(),
+            source: Some("tcdecks".to_owned()),
+            format: Some(Format::Premodern),
+            is_success: false,
+            error_kind: Some(error_kind),
+            error_msg: None,
+            attempts,
+            last_attempt_at: Some(now() - Duration::hours(hours_ago)),
+            created_at: String::new(),
+        }
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2024-05-05 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn failed_links_are_due_once_their_backoff_passed() {
+        let retry = RetryConfig::default();
+
+        assert!(!is_retry_due(
+            &retry,
+            &failed_link(1, FailureKind::Transient, 0),
+            now()
+        ));
+        assert!(is_retry_due(
+            &retry,
+            &failed_link(1, FailureKind::Transient, 1),
+            now()
+        ));
+        assert!(!is_retry_due(
+            &retry,
+            &failed_link(3, FailureKind::Transient, 3),
+            now()
+        ));
+        assert!(is_retry_due(
+            &retry,
+            &failed_link(3, FailureKind::Transient, 4),
+            now()
+        ));
+    }
+
+    #[test]
+    fn failed_links_are_given_up_on() {
+        let retry = RetryConfig::default();
+
+        assert!(!is_retry_due(
+            &retry,
+            &failed_link(5, FailureKind::Transient, 1000),
+            now()
+        ));
+        assert!(!is_retry_due(
+            &retry,
+            &failed_link(1, FailureKind::Parse, 1000),
+            now()
+        ));
+        assert!(is_retry_due(
+            &RetryConfig {
+                retry_parse_errors: true,
+                ..Default::default()
+            },
+            &failed_link(1, FailureKind::Parse, 1000),
+            now()
+        ));
+    }
+
+    #[test]
+    fn links_without_an_attempt_time_are_due() {
+        let scraped = ScrapedLink {
+            last_attempt_at: None,
+            ..failed_link(1, FailureKind::NotFound, 0)
+        };
+
+        assert!(is_due(&RetryConfig::default(), Some(&scraped), now()));
+        assert!(is_due(&RetryConfig::default(), None, now()));
+        assert!(!is_due(
+            &RetryConfig::default(),
+            Some(&ScrapedLink {
+                is_success: true,
+                ..scraped
+            }),
+            now()
+        ));
+    }
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::setup(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn events_are_scraped_while_discovery_goes_on() {
+        let conn = connection();
+        let (scraped, scraped_rx) = mpsc::channel();
+        let sources: Vec<Box<dyn Source>> = vec![Box::new(Pipelined {
+            scraped,
+            scraped_rx: Mutex::new(scraped_rx),
+        })];
+
+        scrape(&conn, &sources, &ScrapeOptions::default()).unwrap();
+
+        assert_eq!(db::find_scraped_links(&conn, "pipelined").unwrap().len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_panicking_discovery_does_not_hang() {
+        let conn = connection();
+        let sources: Vec<Box<dyn Source>> = vec![Box::new(Panicking)];
+
+        let _ = scrape(&conn, &sources, &ScrapeOptions::default());
+    }
+}
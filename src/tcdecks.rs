@@ -1,17 +1,15 @@
 use chrono::prelude::NaiveDate;
-use reqwest::blocking::Client;
 use reqwest::Url;
-use rusqlite::{Connection, Result};
 use select::document::Document;
 use select::node::Children;
 use select::predicate::{Class, Name};
-use std::time::Duration;
-use std::{fmt, thread, time};
 
-use crate::db;
-use crate::deck::{Decklist, Format};
+use crate::config::SourceConfig;
+use crate::deck::{Decklist, DecklistFilter, Event, Format, ScrapedEvent};
+use crate::error::ScrapeError;
+use crate::fetcher::Fetcher;
+use crate::source::Source;
 
-const BASE_URL: &str = "https://www.tcdecks.net";
 const DECKLISTS_ENDPOINT: &str = "/format.php";
 const FORMATS: &[(&str, Format)] = &[
     ("Premodern", Format::Premodern),
@@ -21,108 +19,97 @@ const FORMATS: &[(&str, Format)] = &[
     ("Vintage Old School", Format::OldSchool),
     ("Pauper", Format::Pauper),
 ];
-const SLEEP_DELAY: u64 = 5000;
 
-#[derive(Debug, Clone)]
-struct NotFoundError;
+pub struct Tcdecks {
+    fetcher: Fetcher,
+    config: SourceConfig,
+}
 
-impl fmt::Display for NotFoundError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "decklists not found")
+impl Tcdecks {
+    pub fn new(config: SourceConfig, fetcher: Fetcher) -> Self {
+        Tcdecks { fetcher, config }
     }
 }
-impl std::error::Error for NotFoundError {}
-
-pub fn scrape(conn: &Connection) -> Result<()> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(60))
-        .build()
-        .unwrap();
-
-    for current_format in FORMATS.iter() {
-        let links: Vec<(Format, String)> = match find_latest_decklists(&client, current_format) {
-            Ok(links) => links,
-            Err(e) => {
-                eprintln!("Failed to find decklists for {}: {}", current_format.0, e);
-                vec![]
-            }
-        };
-
-        println!("Found {} links for {}.", links.len(), current_format.0);
-
-        for (index, (format, link)) in links.iter().enumerate() {
-            println!("[{}/{}] {}: {}", index + 1, links.len(), format, link);
-
-            match db::find_scraped_link(conn, link)? {
-                Some(scraped) => {
-                    if scraped.is_success {
-                        println!(
-                            "[{}/{}] Already successfully scraped, skipping",
-                            index + 1,
-                            links.len()
-                        );
-                        continue;
-                    }
-                }
-                None => {}
-            }
 
-            match scrape_decklists(&client, &link, format) {
-                Ok(decklists) => {
-                    for decklist in decklists.into_iter() {
-                        if let Err(e) = db::insert_decklist(conn, &decklist) {
-                            eprintln!("Failed to insert decklist: {}", e);
-                        }
-                    }
+impl Source for Tcdecks {
+    fn name(&self) -> &str {
+        "tcdecks"
+    }
 
-                    db::insert_scraped_link(conn, &link, true, None)?;
+    fn find_links(
+        &self,
+        filter: &DecklistFilter,
+        _is_scraped: &dyn Fn(&str) -> bool,
+        found: &mut dyn FnMut(Format, String),
+    ) -> Result<(), ScrapeError> {
+        // Every format has its own listing, only the requested ones are paged
+        for current_format in FORMATS.iter().filter(|(_, format)| {
+            self.config.is_format_enabled(format) && filter.matches_format(format)
+        }) {
+            match find_latest_decklists(&self.fetcher, &self.config, current_format, found) {
+                Ok(count) => {
+                    println!("Found {} links for {}.", count, current_format.0);
                 }
                 Err(e) => {
-                    eprintln!("Failed to scrape decklists: {}", e);
-                    db::insert_scraped_link(conn, &link, false, Some(&e.to_string()))?;
+                    eprintln!("Failed to find decklists for {}: {}", current_format.0, e);
                 }
             }
         }
+
+        Ok(())
     }
 
-    Ok(())
+    fn scrape_event(
+        &self,
+        link: &str,
+        format: &Format,
+        filter: &DecklistFilter,
+    ) -> Result<ScrapedEvent, ScrapeError> {
+        scrape_decklists(&self.fetcher, &self.config, link, format, filter)
+    }
 }
 
+/// Hands the event links of every listing page to `found` and returns how
+/// many there were.
 fn find_latest_decklists(
-    client: &Client,
+    fetcher: &Fetcher,
+    config: &SourceConfig,
     (format_param, format): &(&str, Format),
-) -> Result<Vec<(Format, String)>, Box<dyn std::error::Error>> {
+    found: &mut dyn FnMut(Format, String),
+) -> Result<usize, ScrapeError> {
     let mut page = 1;
-    let mut links = Vec::new();
+    let mut count = 0;
 
     loop {
-        let url = Url::parse(BASE_URL)?
+        let url = Url::parse(&config.base_url)?
             .join(format!("{DECKLISTS_ENDPOINT}?format={format_param}&page={page}").as_str())?;
 
-        println!(
-            "[{format_param}/{page}] Scanning event links, total {}",
-            links.len()
-        );
+        println!("[{format_param}/{page}] Scanning event links, total {count}");
 
-        let res_html = client.get(url).send()?.text()?;
-        let page_links = parse_tourney_links(res_html, *format);
+        let res_html = fetcher.get(&url)?;
+        let page_links = parse_tourney_links(&res_html, url.as_str(), *format)?;
 
         if page_links.is_empty() || page > 100 {
-            return Ok(links);
+            return Ok(count);
         }
 
-        links.extend(page_links);
+        count += page_links.len();
+        for (format, link) in page_links {
+            found(format, link);
+        }
         page += 1;
-
-        thread::sleep(time::Duration::from_millis(SLEEP_DELAY));
     }
 }
 
-fn parse_tourney_links(res_html: String, format: Format) -> Vec<(Format, String)> {
-    Document::from(res_html.as_str())
+fn parse_tourney_links(
+    res_html: &str,
+    url: &str,
+    format: Format,
+) -> Result<Vec<(Format, String)>, ScrapeError> {
+    let links = Document::from(res_html)
         .find(Class("tourney_list"))
         .next()
-        .unwrap()
+        .ok_or_else(|| ScrapeError::missing(".tourney_list", url))?
         .find(Class("principal"))
         .filter_map(|node| {
             node.find(Name("a")).next().map(|link_node| {
@@ -135,115 +122,213 @@ fn parse_tourney_links(res_html: String, format: Format) -> Vec<(Format, String)
             })
         })
         .flatten()
-        .collect()
+        .collect();
+
+    Ok(links)
+}
+
+/// Event details from the `legend` of event and deck pages. The legend holds
+/// the event name followed by e.g. `Type | Number of Players: 32 | Date: 21/05/2022`.
+#[derive(Default)]
+struct Legend {
+    name: Option<String>,
+    event_type: Option<String>,
+    players: Option<u32>,
+    date: Option<NaiveDate>,
 }
 
 fn scrape_decklists(
-    client: &Client,
+    fetcher: &Fetcher,
+    config: &SourceConfig,
     event_link: &str,
     format: &Format,
-) -> Result<Vec<Decklist>, Box<dyn std::error::Error>> {
-    // Lets be polite
-    thread::sleep(time::Duration::from_millis(SLEEP_DELAY));
-
-    let url = Url::parse(BASE_URL)?.join(event_link)?;
-    let res_html = client.get(url).send()?.text()?;
-
-    let deck_links = parse_tourney_links(res_html, *format);
-
-    let mut decklists = Vec::new();
-
-    for (index, (format, deck_link)) in deck_links.iter().enumerate() {
-        println!(
-            "[Event {event_link}, deck: {}/{}] {}: {}",
-            index + 1,
-            deck_links.len(),
-            format,
-            deck_link
-        );
-
-        let deck_url = Url::parse(BASE_URL)?.join(deck_link)?;
-        let res_html = client.get(deck_url).send()?.text()?;
-
-        let document = Document::from(res_html.as_str());
-
-        let mut legend = document.find(Name("legend")).next().unwrap().children();
-
-        // Skip the empty whitespaces
-        let event = legend.nth(1).map(|node| node.text());
-        let date = legend.nth(1).and_then(|node| {
-            let date_text = node.text();
-            date_text.trim().split(" | ").nth(2).and_then(|date_str| {
-                NaiveDate::parse_from_str(date_str.strip_prefix("Date: ").unwrap_or(""), "%d/%m/%Y")
-                    .ok()
-            })
+    filter: &DecklistFilter,
+) -> Result<ScrapedEvent, ScrapeError> {
+    let url = Url::parse(&config.base_url)?.join(event_link)?;
+    let res_html = fetcher.get(&url)?;
+
+    let deck_links = parse_tourney_links(&res_html, url.as_str(), *format)?;
+    let legend =
+        parse_legend(&Document::from(res_html.as_str()), url.as_str())?.unwrap_or_default();
+
+    if deck_links.is_empty() {
+        return Err(ScrapeError::NotFound {
+            url: url.to_string(),
         });
+    }
 
-        let table = document.find(Name("table")).next().unwrap();
-        let mut rows = table.find(Name("tr"));
+    let mut decklists = Vec::new();
 
-        let mut header_row = rows.next().ok_or("no table rows")?.find(Name("th"));
+    // Every deck is a request of its own, events outside of the requested
+    // dates are returned without them
+    if legend
+        .date
+        .is_none_or(|date| filter.matches_date(Some(date)))
+    {
+        for (index, (format, deck_link)) in deck_links.iter().enumerate() {
+            println!(
+                "[Event {event_link}, deck: {}/{}] {}: {}",
+                index + 1,
+                deck_links.len(),
+                format,
+                deck_link
+            );
+
+            let deck_url = Url::parse(&config.base_url)?.join(deck_link)?;
+            let res_html = fetcher.get(&deck_url)?;
+
+            decklists.push(parse_decklist(&res_html, deck_url.as_str(), format)?);
+        }
+    }
 
-        let name_header = header_row
-            .next()
-            .map(|node| node.text())
-            .ok_or("no name header")?;
+    // Fall back to the first deck page for anything the event page lacks
+    let first = decklists.first();
+    let event = Event {
+        source: "tcdecks".to_owned(),
+        url: event_link.to_owned(),
+        name: legend
+            .name
+            .or_else(|| first.and_then(|decklist| decklist.event.clone()))
+            .map(|name| name.trim().to_owned()),
+        format: *format,
+        date: legend
+            .date
+            .or_else(|| first.and_then(|decklist| decklist.date)),
+        event_type: legend.event_type,
+        players: legend.players,
+    };
+
+    Ok(ScrapedEvent { event, decklists })
+}
 
-        let mut name_and_archetype = name_header.split(" playing ");
-        let player_name = name_and_archetype.next().ok_or("no name")?.trim();
-        let archetype = name_and_archetype.next().ok_or("no archetype")?.trim();
+fn parse_legend(document: &Document, url: &str) -> Result<Option<Legend>, ScrapeError> {
+    let mut children = match document.find(Name("legend")).next() {
+        Some(legend) => legend.children(),
+        None => return Ok(None),
+    };
+
+    // Skip the empty whitespaces
+    let name = children.nth(1).map(|node| node.text());
+    let details = children.nth(1).map(|node| node.text()).unwrap_or_default();
+
+    let mut legend = Legend {
+        name,
+        ..Default::default()
+    };
+
+    for (index, part) in details.trim().split(" | ").enumerate() {
+        let part = part.trim();
+
+        if let Some(date_str) = part.strip_prefix("Date: ") {
+            legend.date = Some(
+                NaiveDate::parse_from_str(date_str, "%d/%m/%Y").map_err(|_| {
+                    ScrapeError::BadDate {
+                        value: date_str.to_owned(),
+                        url: url.to_owned(),
+                    }
+                })?,
+            );
+        } else if part.contains("Players") {
+            legend.players = part.rsplit(' ').next().and_then(|count| count.parse().ok());
+        } else if index == 0 && !part.is_empty() {
+            legend.event_type = Some(part.to_owned());
+        }
+    }
 
-        let position_th = header_row
-            .next()
-            .map(|node| node.text())
-            .ok_or("no position header")?;
+    Ok(Some(legend))
+}
 
-        let position = position_th
-            .trim()
-            .strip_prefix("Position: ")
-            .ok_or("no position")?;
+fn parse_decklist(res_html: &str, url: &str, format: &Format) -> Result<Decklist, ScrapeError> {
+    let document = Document::from(res_html);
 
-        let deck_name_header = rows
-            .next()
-            .ok_or("no table rows")?
-            .find(Name("th"))
-            .next()
-            .map(|node| node.text())
-            .ok_or("no deck name header")?;
+    let legend =
+        parse_legend(&document, url)?.ok_or_else(|| ScrapeError::missing("legend", url))?;
+    let event = legend.name;
+    let date = legend.date;
 
-        let deck_name = deck_name_header
-            .trim()
-            .strip_prefix("Deck Name: ")
-            .and_then(|s| Some(s.to_owned()));
+    let table = document
+        .find(Name("table"))
+        .next()
+        .ok_or_else(|| ScrapeError::missing("table", url))?;
+    let mut rows = table.find(Name("tr"));
 
-        let mut cards_row = rows.next().ok_or("no table rows")?.find(Name("td"));
+    let mut header_row = rows
+        .next()
+        .ok_or_else(|| ScrapeError::missing("table tr", url))?
+        .find(Name("th"));
 
-        let mut mainboard = parse_cards(cards_row.next().ok_or("no cards")?.children());
+    let name_header = header_row
+        .next()
+        .map(|node| node.text())
+        .ok_or_else(|| ScrapeError::missing("tr th (player)", url))?;
 
-        let mut mainboard_2 = parse_cards(cards_row.next().ok_or("no cards")?.children());
+    let mut name_and_archetype = name_header.split(" playing ");
+    let player_name = name_and_archetype.next().unwrap_or_default().trim();
+    let archetype = name_and_archetype
+        .next()
+        .ok_or_else(|| ScrapeError::missing("tr th (archetype)", url))?
+        .trim();
 
-        mainboard.append(&mut mainboard_2);
+    let position_th = header_row
+        .next()
+        .map(|node| node.text())
+        .ok_or_else(|| ScrapeError::missing("tr th (position)", url))?;
 
-        let sideboard = parse_cards(cards_row.next().ok_or("no cards")?.children());
+    let position = position_th
+        .trim()
+        .strip_prefix("Position: ")
+        .ok_or_else(|| ScrapeError::missing("tr th (position)", url))?;
 
-        let decklist = Decklist {
-            event,
-            player: Some(player_name.to_owned()),
-            format: *format,
-            date,
-            mainboard,
-            sideboard,
-            archetype: Some(archetype.to_owned()),
-            result: Some(position.to_owned()),
-            name: deck_name,
-        };
+    let deck_name_header = rows
+        .next()
+        .ok_or_else(|| ScrapeError::missing("table tr (deck name)", url))?
+        .find(Name("th"))
+        .next()
+        .map(|node| node.text())
+        .ok_or_else(|| ScrapeError::missing("tr th (deck name)", url))?;
 
-        decklists.push(decklist);
+    let deck_name = deck_name_header
+        .trim()
+        .strip_prefix("Deck Name: ")
+        .map(|s| s.to_owned());
 
-        thread::sleep(time::Duration::from_millis(SLEEP_DELAY));
-    }
+    let mut cards_row = rows
+        .next()
+        .ok_or_else(|| ScrapeError::missing("table tr (cards)", url))?
+        .find(Name("td"));
 
-    Ok(decklists)
+    let mut next_cards = || {
+        cards_row
+            .next()
+            .map(|node| parse_cards(node.children()))
+            .ok_or_else(|| ScrapeError::missing("tr td (cards)", url))
+    };
+
+    let mut mainboard = next_cards()?;
+    let mut mainboard_2 = next_cards()?;
+
+    mainboard.append(&mut mainboard_2);
+
+    let sideboard = next_cards()?;
+
+    Ok(Decklist {
+        event,
+        player: Some(player_name.to_owned()),
+        format: *format,
+        date,
+        mainboard,
+        sideboard,
+        archetype: Some(archetype.to_owned()),
+        result: Some(position.to_owned()),
+        name: deck_name,
+        source: Some("tcdecks".to_owned()),
+        source_url: Some(url.to_owned()),
+        external_id: Url::parse(url).ok().and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "iddeck")
+                .map(|(_, id)| id.into_owned())
+        }),
+    })
 }
 
 fn parse_cards(card_rows: Children) -> Vec<(usize, String)> {
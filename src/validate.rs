@@ -0,0 +1,412 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::deck::{Decklist, Format, Legality};
+
+/// Cards whose rules text allows more copies than the format would,
+/// with how many.
+const ANY_NUMBER: &[(&str, usize)] = &[
+    ("Relentless Rats", usize::MAX),
+    ("Shadowborn Apostle", usize::MAX),
+    ("Persistent Petitioners", usize::MAX),
+    ("Rat Colony", usize::MAX),
+    ("Dragon's Approach", usize::MAX),
+    ("Slime Against Humanity", usize::MAX),
+    ("Hare Apparent", usize::MAX),
+    ("Templar Knight", usize::MAX),
+    ("Seven Dwarves", 7),
+    ("Nazgûl", 9),
+];
+
+/// Any number of these is allowed, also before `fetch-cards` has stored
+/// their type lines.
+const BASIC_LANDS: &[&str] = &[
+    "Plains",
+    "Island",
+    "Swamp",
+    "Mountain",
+    "Forest",
+    "Wastes",
+    "Snow-Covered Plains",
+    "Snow-Covered Island",
+    "Snow-Covered Swamp",
+    "Snow-Covered Mountain",
+    "Snow-Covered Forest",
+    "Snow-Covered Wastes",
+];
+
+/// Deck construction rules of a format.
+pub struct Rules {
+    pub min_mainboard: usize,
+    pub max_sideboard: usize,
+    /// Copies of a card allowed across main deck and sideboard, 1 for
+    /// singleton formats
+    pub max_copies: usize,
+}
+
+impl Rules {
+    /// `None` for decks whose format we don't know.
+    pub fn for_format(format: &Format) -> Option<Rules> {
+        match format {
+            Format::Unknown => None,
+            // Sites list the commander, or two partners, apart from the other
+            // 99 cards, in the sideboard if at all
+            Format::Commander => Some(Rules {
+                min_mainboard: 99,
+                max_sideboard: 2,
+                max_copies: 1,
+            }),
+            _ => Some(Rules {
+                min_mainboard: 60,
+                max_sideboard: 15,
+                max_copies: 4,
+            }),
+        }
+    }
+}
+
+/// What the validator needs to know about the cards of a format, by name.
+/// Cards without legality data, e.g. ones that aren't resolved yet, are
+/// only checked for their number of copies.
+#[derive(Default)]
+pub struct CardData {
+    pub legalities: HashMap<String, Legality>,
+    pub basic_lands: HashSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    MainboardTooSmall {
+        count: usize,
+        min: usize,
+    },
+    SideboardTooLarge {
+        count: usize,
+        max: usize,
+    },
+    TooManyCopies {
+        card: String,
+        count: usize,
+        max: usize,
+    },
+    Restricted {
+        card: String,
+        count: usize,
+    },
+    Banned {
+        card: String,
+    },
+    NotLegal {
+        card: String,
+    },
+}
+
+impl Violation {
+    /// Stored in `deck_violations.kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::MainboardTooSmall { .. } => "mainboard_too_small",
+            Violation::SideboardTooLarge { .. } => "sideboard_too_large",
+            Violation::TooManyCopies { .. } => "too_many_copies",
+            Violation::Restricted { .. } => "restricted",
+            Violation::Banned { .. } => "banned",
+            Violation::NotLegal { .. } => "not_legal",
+        }
+    }
+
+    pub fn card(&self) -> Option<&str> {
+        match self {
+            Violation::TooManyCopies { card, .. }
+            | Violation::Restricted { card, .. }
+            | Violation::Banned { card }
+            | Violation::NotLegal { card } => Some(card),
+            Violation::MainboardTooSmall { .. } | Violation::SideboardTooLarge { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::MainboardTooSmall { count, min } => {
+                write!(f, "main deck has {} cards, at least {} needed", count, min)
+            }
+            Violation::SideboardTooLarge { count, max } => {
+                write!(f, "sideboard has {} cards, at most {} allowed", count, max)
+            }
+            Violation::TooManyCopies { card, count, max } => {
+                write!(f, "{} copies of {}, at most {} allowed", count, card, max)
+            }
+            Violation::Restricted { card, count } => {
+                write!(f, "{} copies of restricted {}", count, card)
+            }
+            Violation::Banned { card } => write!(f, "{} is banned", card),
+            Violation::NotLegal { card } => write!(f, "{} is not legal", card),
+        }
+    }
+}
+
+/// Checks `decklist` against the rules of its format. Returns no violations
+/// for decks of an unknown format.
+pub fn validate(decklist: &Decklist, cards: &CardData) -> Vec<Violation> {
+    let rules = match Rules::for_format(&decklist.format) {
+        Some(rules) => rules,
+        None => return Vec::new(),
+    };
+
+    let mut violations = Vec::new();
+
+    let mainboard = decklist.mainboard.iter().map(|(count, _)| count).sum();
+    if mainboard < rules.min_mainboard {
+        violations.push(Violation::MainboardTooSmall {
+            count: mainboard,
+            min: rules.min_mainboard,
+        });
+    }
+
+    let sideboard = decklist.sideboard.iter().map(|(count, _)| count).sum();
+    if sideboard > rules.max_sideboard {
+        violations.push(Violation::SideboardTooLarge {
+            count: sideboard,
+            max: rules.max_sideboard,
+        });
+    }
+
+    // Sorted so that violations come out in the same order every time
+    let mut copies = BTreeMap::new();
+    for (count, name) in decklist.mainboard.iter().chain(decklist.sideboard.iter()) {
+        *copies.entry(name.as_str()).or_insert(0) += count;
+    }
+
+    for (card, count) in copies {
+        match cards.legalities.get(card) {
+            Some(Legality::Banned) => {
+                violations.push(Violation::Banned {
+                    card: card.to_owned(),
+                });
+                continue;
+            }
+            Some(Legality::NotLegal) => {
+                violations.push(Violation::NotLegal {
+                    card: card.to_owned(),
+                });
+                continue;
+            }
+            Some(Legality::Restricted) if count > 1 => {
+                violations.push(Violation::Restricted {
+                    card: card.to_owned(),
+                    count,
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        let max = if cards.basic_lands.contains(card) || BASIC_LANDS.contains(&card) {
+            usize::MAX
+        } else {
+            ANY_NUMBER
+                .iter()
+                .find(|(name, _)| *name == card)
+                .map_or(rules.max_copies, |(_, max)| *max)
+        };
+
+        if count > max {
+            violations.push(Violation::TooManyCopies {
+                card: card.to_owned(),
+                count,
+                max,
+            });
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decklist(
+        format: Format,
+        mainboard: &[(usize, &str)],
+        sideboard: &[(usize, &str)],
+    ) -> Decklist {
+        let cards = |cards: &[(usize, &str)]| {
+            cards
+                .iter()
+                .map(|(count, name)| (*count, name.to_string()))
+                .collect()
+        };
+
+        Decklist {
+            format,
+            player: None,
+            event: None,
+            date: None,
+            mainboard: cards(mainboard),
+            sideboard: cards(sideboard),
+            archetype: None,
+            result: None,
+            name: None,
+            source: None,
+            source_url: None,
+            external_id: None,
+        }
+    }
+
+    fn card_data(legalities: &[(&str, Legality)]) -> CardData {
+        CardData {
+            legalities: legalities
+                .iter()
+                .map(|(name, legality)| (name.to_string(), *legality))
+                .collect(),
+            basic_lands: HashSet::new(),
+        }
+    }
+
+    fn legacy(mainboard: &[(usize, &str)], sideboard: &[(usize, &str)]) -> Vec<Violation> {
+        let cards = card_data(&[
+            ("Brainstorm", Legality::Legal),
+            ("Black Lotus", Legality::Banned),
+            ("Ponder", Legality::Legal),
+            ("Lightning Bolt", Legality::Legal),
+        ]);
+
+        validate(&decklist(Format::Legacy, mainboard, sideboard), &cards)
+    }
+
+    #[test]
+    fn legal_decks_have_no_violations() {
+        assert_eq!(
+            legacy(&[(4, "Brainstorm"), (56, "Island")], &[(4, "Ponder")]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn main_deck_too_small() {
+        assert_eq!(
+            legacy(&[(4, "Brainstorm"), (55, "Island")], &[]),
+            vec![Violation::MainboardTooSmall { count: 59, min: 60 }]
+        );
+    }
+
+    #[test]
+    fn sideboard_too_large() {
+        assert_eq!(
+            legacy(&[(60, "Island")], &[(4, "Brainstorm"), (12, "Swamp")]),
+            vec![Violation::SideboardTooLarge { count: 16, max: 15 }]
+        );
+    }
+
+    #[test]
+    fn copies_are_counted_across_main_deck_and_sideboard() {
+        assert_eq!(
+            legacy(&[(4, "Ponder"), (56, "Island")], &[(1, "Ponder")]),
+            vec![Violation::TooManyCopies {
+                card: "Ponder".to_owned(),
+                count: 5,
+                max: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn banned_cards() {
+        assert_eq!(
+            legacy(&[(1, "Black Lotus"), (59, "Island")], &[]),
+            vec![Violation::Banned {
+                card: "Black Lotus".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn restricted_cards_are_allowed_once() {
+        let cards = card_data(&[("Ancestral Recall", Legality::Restricted)]);
+        let deck = |count| {
+            decklist(
+                Format::Vintage,
+                &[(count, "Ancestral Recall"), (59, "Island")],
+                &[],
+            )
+        };
+
+        assert_eq!(validate(&deck(1), &cards), vec![]);
+        assert_eq!(
+            validate(&deck(2), &cards),
+            vec![Violation::Restricted {
+                card: "Ancestral Recall".to_owned(),
+                count: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn cards_not_legal_in_the_format() {
+        let cards = card_data(&[("Tarmogoyf", Legality::NotLegal)]);
+
+        assert_eq!(
+            validate(
+                &decklist(Format::Premodern, &[(4, "Tarmogoyf"), (56, "Forest")], &[]),
+                &cards
+            ),
+            vec![Violation::NotLegal {
+                card: "Tarmogoyf".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn some_cards_allow_more_copies() {
+        assert_eq!(
+            legacy(&[(30, "Relentless Rats"), (30, "Swamp")], &[]),
+            vec![]
+        );
+        assert_eq!(
+            legacy(&[(8, "Seven Dwarves"), (52, "Mountain")], &[]),
+            vec![Violation::TooManyCopies {
+                card: "Seven Dwarves".to_owned(),
+                count: 8,
+                max: 7,
+            }]
+        );
+    }
+
+    #[test]
+    fn basic_lands_are_known_without_card_data() {
+        let deck = decklist(
+            Format::Legacy,
+            &[(20, "Snow-Covered Island"), (40, "Island")],
+            &[],
+        );
+
+        assert_eq!(validate(&deck, &CardData::default()), vec![]);
+    }
+
+    #[test]
+    fn commander_decks_are_singleton() {
+        let deck = decklist(
+            Format::Commander,
+            &[(2, "Sol Ring"), (1, "Relentless Rats"), (96, "Island")],
+            &[(1, "Atraxa, Praetors' Voice")],
+        );
+
+        assert_eq!(
+            validate(&deck, &CardData::default()),
+            vec![Violation::TooManyCopies {
+                card: "Sol Ring".to_owned(),
+                count: 2,
+                max: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn decks_of_unknown_formats_are_not_checked() {
+        let deck = decklist(Format::Unknown, &[(10, "Black Lotus")], &[]);
+
+        assert_eq!(validate(&deck, &card_data(&[])), vec![]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Imports a small `default-cards` file the way `fetch-cards --file` does.
+
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use std::path::Path;
+
+use deck_list_scraper::cards::{self, ImportStats};
+use deck_list_scraper::db;
+
+const DEFAULT_CARDS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/scryfall/default-cards.json"
+);
+
+/// Lightning Bolt under a new name, same oracle id
+const RENAMED_CARDS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/scryfall/renamed-cards.json"
+);
+
+fn import() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+    cards::import_scryfall_cards(&conn, Path::new(DEFAULT_CARDS)).unwrap();
+
+    conn
+}
+
+fn query<T: rusqlite::types::FromSql>(conn: &Connection, sql: &str, name: &str) -> T {
+    conn.query_row(sql, [name], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn imports_cards_faces_and_legalities() {
+    let conn = import();
+
+    // Two printings of Lightning Bolt are one card, the entry that isn't a
+    // card is skipped
+    let count: usize = conn
+        .query_row("SELECT COUNT(*) FROM cards", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 3);
+
+    let (_, name, resolved) = db::find_card(&conn, "Delver of Secrets").unwrap().unwrap();
+    assert_eq!(name, "Delver of Secrets // Insectile Aberration");
+    assert!(resolved);
+
+    let faces: String = query(
+        &conn,
+        "SELECT group_concat(card_faces.name, ' / ') FROM card_faces
+            JOIN cards ON cards.id = card_faces.card_id
+            WHERE cards.name = ?1
+            ORDER BY face_index",
+        "Fire // Ice",
+    );
+    assert_eq!(faces, "Fire / Ice");
+
+    let legality: String = query(
+        &conn,
+        "SELECT legality FROM card_legalities
+            JOIN cards ON cards.id = card_legalities.card_id
+            WHERE cards.name = ?1 AND format = 'premodern'",
+        "Fire // Ice",
+    );
+    assert_eq!(legality, "legal");
+}
+
+#[test]
+fn keeps_the_cheapest_printing_per_currency() {
+    let conn = import();
+
+    let prices: (f64, f64, f64) = conn
+        .query_row(
+            "SELECT usd, eur, tix FROM card_prices
+                JOIN cards ON cards.id = card_prices.card_id
+                WHERE cards.name = 'Lightning Bolt'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+
+    assert_eq!(prices, (1.25, 1.8, 0.02));
+}
+
+#[test]
+fn printings_after_the_first_only_add_prices() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+
+    let stats = cards::import_scryfall_cards(&conn, Path::new(DEFAULT_CARDS)).unwrap();
+    assert_eq!(
+        stats,
+        ImportStats {
+            inserted: 3,
+            ..Default::default()
+        }
+    );
+
+    let set_code: String = query(
+        &conn,
+        "SELECT set_code FROM cards WHERE name = ?1",
+        "Lightning Bolt",
+    );
+    assert_eq!(set_code, "m10");
+}
+
+#[test]
+fn importing_again_changes_nothing() {
+    let conn = import();
+    // Every column of every table the import writes
+    let snapshot = |conn: &Connection| -> Vec<Vec<Value>> {
+        ["cards", "card_faces", "card_legalities", "card_prices"]
+            .iter()
+            .flat_map(|table| {
+                let mut stmt = conn
+                    .prepare(&format!("SELECT * FROM {table} ORDER BY 1, 2"))
+                    .unwrap();
+                let columns = stmt.column_count();
+                stmt.query_map([], |row| (0..columns).map(|i| row.get(i)).collect())
+                    .unwrap()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap()
+            })
+            .collect()
+    };
+
+    let before = snapshot(&conn);
+    let stats = cards::import_scryfall_cards(&conn, Path::new(DEFAULT_CARDS)).unwrap();
+
+    assert_eq!(
+        stats,
+        ImportStats {
+            unchanged: 3,
+            ..Default::default()
+        }
+    );
+    assert_eq!(snapshot(&conn), before);
+}
+
+#[test]
+fn renamed_cards_take_over_rows_with_the_new_name() {
+    let conn = import();
+    let bolt = db::find_card(&conn, "Lightning Bolt").unwrap().unwrap().0;
+
+    // Scraped before the rename, and left over from another card
+    let scraped = db::resolve_card(&conn, "bolt of lightning").unwrap();
+    conn.execute(
+        "INSERT INTO cards (name, scryfall_id, oracle_id)
+            VALUES ('Bolt of Lightning', 'old-printing', 'old-oracle-id')",
+        [],
+    )
+    .unwrap();
+
+    cards::import_scryfall_cards(&conn, Path::new(RENAMED_CARDS)).unwrap();
+
+    let count: usize = conn
+        .query_row("SELECT COUNT(*) FROM cards", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 3);
+
+    let (card_id, name, _) = db::find_card(&conn, "Bolt of Lightning").unwrap().unwrap();
+    assert_eq!((card_id, name.as_str()), (bolt, "Bolt of Lightning"));
+    assert_eq!(
+        db::find_card(&conn, "Lightning Bolt").unwrap().unwrap().0,
+        bolt
+    );
+    assert_ne!(scraped, bolt);
+
+    let oracle_id: String = query(
+        &conn,
+        "SELECT oracle_id FROM cards WHERE name = ?1",
+        "Bolt of Lightning",
+    );
+    assert_eq!(oracle_id, "4457ed35-7c10-48c8-9776-456485fdf070");
+}
+
+#[test]
+fn aliased_cards_get_the_scryfall_data() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+
+    // Left by `merge-cards` before it normalized the name it kept
+    conn.execute_batch(
+        "INSERT INTO cards (id, name) VALUES (1, 'Fire/Ice');
+        INSERT INTO card_aliases (alias, card_id) VALUES ('fire // ice', 1);",
+    )
+    .unwrap();
+
+    cards::import_scryfall_cards(&conn, Path::new(DEFAULT_CARDS)).unwrap();
+
+    assert_eq!(db::count_cards(&conn).unwrap(), (3, 0));
+    assert_eq!(
+        db::find_card(&conn, "fire/ice").unwrap(),
+        Some((1, "Fire // Ice".to_owned(), true))
+    );
+}
+
+#[test]
+fn cards_that_fail_leave_nothing_behind() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+
+    // Fails Fire // Ice after its row and faces are written
+    conn.execute_batch(
+        "CREATE TRIGGER fail_fire_ice BEFORE INSERT ON card_legalities
+            WHEN (SELECT name FROM cards WHERE id = NEW.card_id) = 'Fire // Ice'
+            BEGIN SELECT RAISE(ABORT, 'failed on purpose'); END",
+    )
+    .unwrap();
+
+    cards::import_scryfall_cards(&conn, Path::new(DEFAULT_CARDS)).unwrap();
+
+    assert!(db::find_card(&conn, "Fire // Ice").unwrap().is_none());
+    let faces: usize = conn
+        .query_row("SELECT COUNT(*) FROM card_faces", [], |row| row.get(0))
+        .unwrap();
+    // Delver's, none of Fire // Ice
+    assert_eq!(faces, 2);
+    assert!(db::find_card(&conn, "Lightning Bolt").unwrap().is_some());
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+
+    assert!(cards::import_scryfall_cards(&conn, Path::new("does-not-exist.json")).is_err());
+}
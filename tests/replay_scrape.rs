@@ -0,0 +1,165 @@
+//! Scrapes both sources from the pages in `tests/fixtures/replay`, the way
+//! `--http-mode replay` does, without touching the network.
+
+use chrono::NaiveDate;
+use reqwest::Url;
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use deck_list_scraper::config::Config;
+use deck_list_scraper::db;
+use deck_list_scraper::deck::{DecklistFilter, Format};
+use deck_list_scraper::error::ScrapeError;
+use deck_list_scraper::fetcher::{Fetcher, ReplayTransport, Transport};
+use deck_list_scraper::mtgo::Mtgo;
+use deck_list_scraper::source::{self, ScrapeOptions, Source};
+use deck_list_scraper::tcdecks::Tcdecks;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/replay");
+
+/// Replays the fixtures and remembers the urls that were requested.
+struct LoggedReplay {
+    replay: ReplayTransport,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Transport for LoggedReplay {
+    fn get(&self, url: &Url) -> Result<String, ScrapeError> {
+        self.requests.lock().unwrap().push(url.to_string());
+        self.replay.get(url)
+    }
+}
+
+fn sources() -> Vec<Box<dyn Source>> {
+    logged_sources(&Arc::default())
+}
+
+fn logged_sources(requests: &Arc<Mutex<Vec<String>>>) -> Vec<Box<dyn Source>> {
+    let mut config = Config::default();
+    config.tcdecks.formats = vec![Format::Premodern];
+
+    let fetcher = |source| {
+        let transport = LoggedReplay {
+            replay: ReplayTransport::new(Path::new(FIXTURES)),
+            requests: Arc::clone(requests),
+        };
+        Fetcher::new(source, Box::new(transport))
+    };
+
+    vec![
+        Box::new(Tcdecks::new(config.tcdecks.clone(), fetcher("tcdecks"))),
+        Box::new(Mtgo::new(config.mtgo.clone(), fetcher("mtgo"))),
+    ]
+}
+
+/// Scrapes into a new database and returns it with its decks as JSON, sorted
+/// since the order they are saved in depends on the worker threads.
+fn scrape() -> (Connection, Vec<String>) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+
+    let options = ScrapeOptions {
+        concurrency: 2,
+        ..Default::default()
+    };
+    source::scrape(&conn, &sources(), &options).unwrap();
+
+    let mut decklists = db::find_decklists(&conn, &DecklistFilter::default())
+        .unwrap()
+        .iter()
+        .map(|decklist| serde_json::to_string(decklist).unwrap())
+        .collect::<Vec<_>>();
+    decklists.sort();
+
+    (conn, decklists)
+}
+
+#[test]
+fn scrapes_both_sources_from_fixtures() {
+    let (conn, decklists) = scrape();
+
+    let mut by_format = db::count_decks_by_format(&conn).unwrap();
+    by_format.sort();
+    assert_eq!(
+        by_format,
+        vec![
+            ("legacy".to_owned(), 2),
+            ("modern".to_owned(), 1),
+            ("premodern".to_owned(), 2),
+        ]
+    );
+    assert_eq!(db::count_events(&conn).unwrap(), 3);
+    assert_eq!(decklists.len(), 5);
+
+    let (scraped, failed) = db::count_scraped_links(&conn).unwrap();
+    assert_eq!((scraped, failed), (3, 0));
+
+    // Spellings are normalized on the way in
+    let name = |name| db::find_card(&conn, name).unwrap().map(|(_, name, _)| name);
+    assert_eq!(name("fire/ice").as_deref(), Some("Fire // Ice"));
+    assert_eq!(name("Æther Vial").as_deref(), Some("Aether Vial"));
+}
+
+#[test]
+fn scraping_twice_gives_the_same_decks() {
+    assert_eq!(scrape().1, scrape().1);
+}
+
+#[test]
+fn scraping_again_skips_scraped_events() {
+    let (conn, decklists) = scrape();
+
+    source::scrape(&conn, &sources(), &ScrapeOptions::default()).unwrap();
+
+    assert_eq!(
+        db::find_decklists(&conn, &DecklistFilter::default())
+            .unwrap()
+            .len(),
+        decklists.len()
+    );
+    assert_eq!(db::count_events(&conn).unwrap(), 3);
+}
+
+fn scrape_filtered(filter: DecklistFilter) -> (Connection, Vec<String>) {
+    let conn = Connection::open_in_memory().unwrap();
+    db::setup(&conn).unwrap();
+
+    let requests = Arc::default();
+    let options = ScrapeOptions {
+        filter,
+        ..Default::default()
+    };
+    source::scrape(&conn, &logged_sources(&requests), &options).unwrap();
+
+    let requests = requests.lock().unwrap().clone();
+    (conn, requests)
+}
+
+#[test]
+fn other_formats_are_not_paged() {
+    let (conn, requests) = scrape_filtered(DecklistFilter {
+        formats: vec![Format::Legacy],
+        ..Default::default()
+    });
+
+    assert!(requests.iter().all(|url| !url.contains("tcdecks")));
+    assert_eq!(
+        db::count_decks_by_format(&conn).unwrap(),
+        vec![("legacy".to_owned(), 2)]
+    );
+}
+
+#[test]
+fn decks_of_events_outside_the_dates_are_not_fetched() {
+    let (conn, requests) = scrape_filtered(DecklistFilter {
+        formats: vec![Format::Premodern],
+        since: NaiveDate::from_ymd_opt(2023, 1, 1),
+        ..Default::default()
+    });
+
+    // The listing and the event page, which dates the event
+    assert!(requests.iter().any(|url| url.contains("deck.php?id=100")));
+    assert!(requests.iter().all(|url| !url.contains("iddeck")));
+    assert_eq!(db::count_events(&conn).unwrap(), 0);
+}